@@ -31,6 +31,7 @@ class Illumination(QChildWindow):
         self.radios = {
             "MODEL_1": QRadioButton("Model 1"),
             "MODEL_2": QRadioButton("Model 2"),
+            "BLINN_PHONG": QRadioButton("Blinn-Phong"),
             "FLAT": QRadioButton("Flat"),
         }
         self.show_content()
@@ -55,7 +56,7 @@ class Illumination(QChildWindow):
             return
 
         selected = selected_radio.text().lower()
-        model = 0 if selected == "flat" else 1 if selected == "model 1" else 2
+        model = {"flat": 0, "model 1": 1, "model 2": 2, "blinn-phong": 3}[selected]
 
         self.canvas.setPixmap(self.backup_pixmap)
         image_canvas = qto.get_image_from_canvas(self.canvas)
@@ -98,12 +99,15 @@ class Illumination(QChildWindow):
             lambda: self.set_radio_selected("MODEL_1"))
         self.radios["MODEL_2"].clicked.connect(
             lambda: self.set_radio_selected("MODEL_2"))
+        self.radios["BLINN_PHONG"].clicked.connect(
+            lambda: self.set_radio_selected("BLINN_PHONG"))
 
         self.radios["FLAT"].setChecked(True)
 
         grid.addWidget(self.radios["FLAT"], 5, 0)
         grid.addWidget(self.radios["MODEL_1"], 5, 1)
         grid.addWidget(self.radios["MODEL_2"], 5, 2)
+        grid.addWidget(self.radios["BLINN_PHONG"], 6, 2)
         kd_1_label = QLabel("Kd 1")
         kd_1_label.setAlignment(Qt.AlignCenter)
         kd_1_input = QLineEdit()
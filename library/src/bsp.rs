@@ -0,0 +1,298 @@
+use crate::camera::Camera;
+use crate::common::{HomogeneousPoint, Image, Rgba, Vector3};
+use crate::mesh::Mesh;
+use crate::operations::{view_setup, viewer_point};
+use crate::painter::{fill_convex_polygon, project_polygon, DepthPolygon};
+use crate::viewport::WindowViewport;
+
+use pyo3::prelude::*;
+
+//distance to a plane under which a point is taken as lying on it
+const PLANE_EPSILON: f64 = 1e-9;
+//how many polygons are tried as the partition plane of each node, the one that splits the fewest others and
+//leaves the sides most even is used
+const SPLITTER_CANDIDATES: usize = 5;
+//how many of the polygons of a node the candidates are scored against, so choosing stays linear
+const SPLITTER_SAMPLE: usize = 64;
+//how many polygons on the unbalanced side weigh as much as splitting one polygon
+const SPLIT_COST: usize = 8;
+
+#[derive(Clone)]
+struct BspNode {
+    //the polygon whose plane divides the space first, then the ones lying on the same plane
+    polygons: Vec<DepthPolygon>,
+    //indexes of the children in the nodes of the tree
+    front: Option<usize>,
+    back: Option<usize>,
+}
+
+//whether a polygon has points in front of the plane of the splitter, and behind it
+fn sides(polygon: &DepthPolygon, splitter: &DepthPolygon) -> (bool, bool) {
+    let distances = polygon.points.iter().map(|point| splitter.distance(*point));
+    distances.fold((false, false), |(front, back), d| {
+        (front || d > PLANE_EPSILON, back || d < -PLANE_EPSILON)
+    })
+}
+
+fn choose_splitter(polygons: &[DepthPolygon]) -> usize {
+    let step = (polygons.len() / SPLITTER_SAMPLE).max(1);
+    (0..polygons.len().min(SPLITTER_CANDIDATES))
+        .min_by_key(|i| {
+            let (mut splits, mut front, mut back) = (0, 0, 0);
+            for polygon in polygons.iter().step_by(step) {
+                match sides(polygon, &polygons[*i]) {
+                    (true, true) => splits += 1,
+                    (true, false) => front += 1,
+                    (false, true) => back += 1,
+                    (false, false) => {}
+                }
+            }
+            splits * SPLIT_COST + front.max(back) - front.min(back)
+        })
+        .unwrap_or(0)
+}
+
+//polygons waiting for a node, with the node and the side of it the new node hangs from
+type Pending = (Vec<DepthPolygon>, Option<(usize, bool)>);
+
+//build the nodes of a tree with the root first. the polygons waiting for a node are kept in a stack,
+//so deep trees do not use up the call stack
+fn build_nodes(polygons: Vec<DepthPolygon>) -> Vec<BspNode> {
+    let mut nodes: Vec<BspNode> = vec![];
+    let mut pending: Vec<Pending> = vec![(polygons, None)];
+    while let Some((mut polygons, parent)) = pending.pop() {
+        if polygons.is_empty() {
+            continue;
+        }
+        let splitter = polygons.swap_remove(choose_splitter(&polygons));
+
+        let mut node_polygons: Vec<DepthPolygon> = vec![splitter];
+        let (mut front, mut back): (Vec<DepthPolygon>, Vec<DepthPolygon>) = (vec![], vec![]);
+        for polygon in polygons {
+            match sides(&polygon, &node_polygons[0]) {
+                (true, true) => match polygon.split(&node_polygons[0]) {
+                    Some((front_piece, back_piece)) => {
+                        front.push(front_piece);
+                        back.push(back_piece);
+                    }
+                    None => front.push(polygon),
+                },
+                (true, false) => front.push(polygon),
+                (false, true) => back.push(polygon),
+                (false, false) => node_polygons.push(polygon),
+            }
+        }
+
+        let index = nodes.len();
+        nodes.push(BspNode {
+            polygons: node_polygons,
+            front: None,
+            back: None,
+        });
+        match parent {
+            Some((parent, true)) => nodes[parent].front = Some(index),
+            Some((parent, false)) => nodes[parent].back = Some(index),
+            None => {}
+        }
+        pending.push((front, Some((index, true))));
+        pending.push((back, Some((index, false))));
+    }
+    nodes
+}
+
+//what is left to visit when walking the tree: a whole node, or only the polygons on the plane of one
+enum Visit {
+    Node(usize),
+    Polygons(usize),
+}
+
+//visit the tree from the farthest polygons to the closest ones to the viewer.
+//back faces are left out when cull is set
+fn back_to_front(nodes: &[BspNode], viewer: HomogeneousPoint, cull: bool) -> Vec<&DepthPolygon> {
+    let mut ordered: Vec<&DepthPolygon> = vec![];
+    let mut stack: Vec<Visit> = match nodes.is_empty() {
+        true => vec![],
+        false => vec![Visit::Node(0)],
+    };
+    while let Some(visit) = stack.pop() {
+        match visit {
+            Visit::Node(index) => {
+                let node = &nodes[index];
+                let viewer_in_front = node.polygons[0].side_of(viewer) >= 0.;
+                let (far, near) = match viewer_in_front {
+                    true => (node.back, node.front),
+                    false => (node.front, node.back),
+                };
+                //the last one pushed is visited first
+                stack.extend(near.map(Visit::Node));
+                stack.push(Visit::Polygons(index));
+                stack.extend(far.map(Visit::Node));
+            }
+            //polygons on the same plane can face opposite ways
+            Visit::Polygons(index) => ordered.extend(
+                nodes[index]
+                    .polygons
+                    .iter()
+                    .filter(|polygon| !cull || polygon.side_of(viewer) > 0.),
+            ),
+        }
+    }
+    ordered
+}
+
+//a binary space partitioning tree of the faces of a mesh, in world coordinates. it is built once for a scene
+//that does not change, and gives the faces in visibility order from any viewer. the faces that go through
+//the plane of a node are split in two
+#[pyclass]
+#[derive(Clone)]
+pub struct BspTree {
+    nodes: Vec<BspNode>,
+    #[pyo3(get)]
+    pub cull_back_faces: bool,
+}
+
+impl BspTree {
+    pub fn from_mesh(mesh: &Mesh) -> BspTree {
+        let polygons: Vec<DepthPolygon> = mesh
+            .faces
+            .iter()
+            .filter_map(|face| {
+                let points = [face.0, face.1, face.2].map(|index| {
+                    let (x, y, z, w) = mesh.vertices[index];
+                    (x / w, y / w, z / w)
+                });
+                DepthPolygon::new(points.to_vec(), mesh.face_color(face))
+            })
+            .collect();
+        BspTree {
+            nodes: build_nodes(polygons),
+            cull_back_faces: mesh.cull_back_faces,
+        }
+    }
+
+    //the polygons from back to front for a viewer, a point or a direction with w = 0
+    pub fn order(&self, viewer: HomogeneousPoint) -> Vec<&DepthPolygon> {
+        back_to_front(&self.nodes, viewer, self.cull_back_faces)
+    }
+
+    //draw the polygons in visibility order, each one with a flat color
+    pub fn draw(
+        &self,
+        image: Image,
+        camera: Option<&Camera>,
+        viewport: Option<&WindowViewport>,
+    ) -> Image {
+        let mut new_image: Image = image.clone();
+        let (matrix, area) = view_setup(image[0].len(), image.len(), camera, viewport);
+        let viewer = match viewer_point(&matrix) {
+            Some(viewer) => viewer,
+            None => return new_image,
+        };
+        for polygon in self.order(viewer) {
+            let points: Vec<HomogeneousPoint> =
+                polygon.points.iter().map(|p| (p.0, p.1, p.2, 1.)).collect();
+            if let Some(projected) = project_polygon(&points, polygon.color, &matrix, &area) {
+                fill_convex_polygon(&mut new_image, &projected.outline(), &projected.color);
+            }
+        }
+        new_image
+    }
+}
+
+#[pymethods]
+impl BspTree {
+    #[new]
+    fn new(mesh: &Mesh) -> Self {
+        BspTree::from_mesh(mesh)
+    }
+
+    //how many polygons the tree has, counting the pieces of the split ones
+    #[getter]
+    fn polygon_count(&self) -> usize {
+        self.nodes.iter().map(|node| node.polygons.len()).sum()
+    }
+
+    //the polygons from back to front seen from the eye, as their corners and color
+    #[pyo3(name = "order")]
+    fn py_order(&self, eye: Vector3) -> Vec<(Vec<Vector3>, Rgba)> {
+        self.order((eye.0, eye.1, eye.2, 1.))
+            .iter()
+            .map(|polygon| (polygon.points.clone(), polygon.color))
+            .collect()
+    }
+
+    #[pyo3(name = "draw")]
+    #[args(camera = "None", viewport = "None")]
+    fn py_draw(
+        &self,
+        image: Image,
+        camera: Option<Camera>,
+        viewport: Option<WindowViewport>,
+    ) -> Image {
+        self.draw(image, camera.as_ref(), viewport.as_ref())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::consts::PI;
+
+    fn square(z: f64) -> [[HomogeneousPoint; 3]; 2] {
+        [
+            [(0., 0., z, 1.), (1., 0., z, 1.), (1., 1., z, 1.)],
+            [(0., 0., z, 1.), (1., 1., z, 1.), (0., 1., z, 1.)],
+        ]
+    }
+
+    #[test]
+    fn farthest_polygons_come_first() {
+        let mut triangles = square(0.).to_vec();
+        triangles.extend(square(1.));
+        let tree = BspTree::from_mesh(&Mesh::from_triangles(&triangles, [255, 0, 0, 255]));
+        for (eye_z, first_z) in [(-5., 1.), (5., 0.)] {
+            let order = tree.order((0.5, 0.5, eye_z, 1.));
+            assert_eq!(order.len(), 4);
+            assert!(order[..2]
+                .iter()
+                .all(|polygon| polygon.points[0].2 == first_z));
+        }
+    }
+
+    #[test]
+    fn convex_meshes_with_many_faces_do_not_overflow() {
+        //every face of a sphere has all the others on one side, so the tree is a chain as deep as the faces
+        let (rings, segments) = (40, 90);
+        let at = |ring: usize, segment: usize| {
+            let (theta, phi) = (
+                PI * ring as f64 / rings as f64,
+                2. * PI * segment as f64 / segments as f64,
+            );
+            (
+                theta.sin() * phi.cos(),
+                theta.sin() * phi.sin(),
+                theta.cos(),
+                1.,
+            )
+        };
+        let mut triangles = vec![];
+        for ring in 0..rings {
+            for segment in 0..segments {
+                let corners = [
+                    at(ring, segment),
+                    at(ring + 1, segment),
+                    at(ring + 1, segment + 1),
+                    at(ring, segment + 1),
+                ];
+                triangles.push([corners[0], corners[1], corners[2]]);
+                triangles.push([corners[0], corners[2], corners[3]]);
+            }
+        }
+        let mesh = Mesh::from_triangles(&triangles, [255, 0, 0, 255]);
+        let tree = BspTree::from_mesh(&mesh);
+        //the two triangles of each quad lie on the same plane and share a node
+        assert_eq!(tree.nodes.len(), rings * segments);
+        let total: usize = tree.nodes.iter().map(|node| node.polygons.len()).sum();
+        assert_eq!(tree.order((0., 0., -5., 1.)).len(), total);
+    }
+}
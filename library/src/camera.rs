@@ -0,0 +1,259 @@
+use crate::common::Vector3;
+use crate::transform::matrix_to_array;
+use crate::vector::{cross, dot, length, normalize, subtract};
+
+use ndarray::{arr2, ArrayBase, Dim, OwnedRepr};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+//a perspective camera. the view matrix follows the look-at convention, so the camera looks down its -z axis,
+//and the projection maps the frustum to the clip space volume -w <= x, y, z <= w.
+//all matrices are meant to multiply row vectors, like the rest of the library
+#[pyclass]
+#[derive(Clone)]
+pub struct Camera {
+    #[pyo3(get)]
+    pub eye: Vector3,
+    #[pyo3(get)]
+    pub target: Vector3,
+    #[pyo3(get)]
+    pub up: Vector3,
+    //vertical field of view, in degrees
+    #[pyo3(get)]
+    pub fov: f64,
+    //width / height of the viewport
+    #[pyo3(get)]
+    pub aspect: f64,
+    #[pyo3(get)]
+    pub near: f64,
+    #[pyo3(get)]
+    pub far: f64,
+}
+
+impl Camera {
+    //what keeps the camera from having a view and a projection, None when it has both.
+    //the checks are written so that nan values fail them too
+    pub fn problem(&self) -> Option<String> {
+        if self.fov.is_nan() || self.fov <= 0. || self.fov >= 180. {
+            return Some(String::from(
+                "the field of view must be between 0 and 180 degrees",
+            ));
+        }
+        if self.aspect.is_nan() || self.aspect <= 0. {
+            return Some(String::from("the aspect must be positive"));
+        }
+        if self.near.is_nan() || self.near <= 0. {
+            return Some(String::from("the near plane must be in front of the eye"));
+        }
+        if self.far.is_nan() || self.far == self.near {
+            return Some(String::from("the near and far planes can not be the same"));
+        }
+        let forward = subtract(self.target, self.eye);
+        if length(forward).is_nan() || length(forward) < f64::EPSILON {
+            return Some(String::from("the eye and the target can not be the same"));
+        }
+        //also true for a zero up vector
+        let side = length(cross(forward, self.up));
+        if side.is_nan() || side <= f64::EPSILON * length(forward) * length(self.up) {
+            return Some(String::from(
+                "the up vector can not be parallel to the view direction",
+            ));
+        }
+        None
+    }
+
+    //keep a change to the camera only when the camera is still usable after it
+    fn change(&mut self, change: impl FnOnce(&mut Camera)) -> PyResult<()> {
+        let mut camera = self.clone();
+        change(&mut camera);
+        check_camera(&camera)?;
+        *self = camera;
+        Ok(())
+    }
+
+    pub fn view_matrix(&self) -> ArrayBase<OwnedRepr<f64>, Dim<[usize; 2]>> {
+        let forward = normalize(subtract(self.target, self.eye));
+        let side = normalize(cross(forward, self.up));
+        let up = cross(side, forward);
+        // rustfmt-ignore
+        let matrix: [[f64; 4]; 4] = [
+            [side.0, up.0, -forward.0, 0.],
+            [side.1, up.1, -forward.1, 0.],
+            [side.2, up.2, -forward.2, 0.],
+            [
+                -dot(side, self.eye),
+                -dot(up, self.eye),
+                dot(forward, self.eye),
+                1.,
+            ],
+        ];
+        arr2(&matrix)
+    }
+
+    pub fn projection_matrix(&self) -> ArrayBase<OwnedRepr<f64>, Dim<[usize; 2]>> {
+        let f = 1. / (self.fov.to_radians() / 2.).tan();
+        let depth = self.near - self.far;
+        // rustfmt-ignore
+        let matrix: [[f64; 4]; 4] = [
+            [f / self.aspect, 0., 0., 0.],
+            [0., f, 0., 0.],
+            [0., 0., (self.far + self.near) / depth, -1.],
+            [0., 0., 2. * self.far * self.near / depth, 0.],
+        ];
+        arr2(&matrix)
+    }
+
+    //world coordinates to clip space
+    pub fn view_projection_matrix(&self) -> ArrayBase<OwnedRepr<f64>, Dim<[usize; 2]>> {
+        self.view_matrix().dot(&self.projection_matrix())
+    }
+}
+
+pub fn check_camera(camera: &Camera) -> PyResult<()> {
+    match camera.problem() {
+        Some(problem) => Err(PyValueError::new_err(problem)),
+        None => Ok(()),
+    }
+}
+
+#[pymethods]
+impl Camera {
+    #[new]
+    #[args(
+        up = "(0., 1., 0.)",
+        fov = "60.",
+        aspect = "1.",
+        near = "0.1",
+        far = "1000."
+    )]
+    fn new(
+        eye: Vector3,
+        target: Vector3,
+        up: Vector3,
+        fov: f64,
+        aspect: f64,
+        near: f64,
+        far: f64,
+    ) -> PyResult<Self> {
+        let camera = Camera {
+            eye,
+            target,
+            up,
+            fov,
+            aspect,
+            near,
+            far,
+        };
+        check_camera(&camera)?;
+        Ok(camera)
+    }
+
+    #[setter]
+    fn set_eye(&mut self, eye: Vector3) -> PyResult<()> {
+        self.change(|camera| camera.eye = eye)
+    }
+
+    #[setter]
+    fn set_target(&mut self, target: Vector3) -> PyResult<()> {
+        self.change(|camera| camera.target = target)
+    }
+
+    #[setter]
+    fn set_up(&mut self, up: Vector3) -> PyResult<()> {
+        self.change(|camera| camera.up = up)
+    }
+
+    #[setter]
+    fn set_fov(&mut self, fov: f64) -> PyResult<()> {
+        self.change(|camera| camera.fov = fov)
+    }
+
+    #[setter]
+    fn set_aspect(&mut self, aspect: f64) -> PyResult<()> {
+        self.change(|camera| camera.aspect = aspect)
+    }
+
+    #[setter]
+    fn set_near(&mut self, near: f64) -> PyResult<()> {
+        self.change(|camera| camera.near = near)
+    }
+
+    #[setter]
+    fn set_far(&mut self, far: f64) -> PyResult<()> {
+        self.change(|camera| camera.far = far)
+    }
+
+    #[pyo3(name = "view_matrix")]
+    fn py_view_matrix(&self) -> [[f64; 4]; 4] {
+        matrix_to_array(self.view_matrix())
+    }
+
+    #[pyo3(name = "projection_matrix")]
+    fn py_projection_matrix(&self) -> [[f64; 4]; 4] {
+        matrix_to_array(self.projection_matrix())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::operations::transform_point_homogeneous;
+
+    fn camera() -> Camera {
+        Camera {
+            eye: (0., 0., 10.),
+            target: (0., 0., 0.),
+            up: (0., 1., 0.),
+            fov: 90.,
+            aspect: 2.,
+            near: 1.,
+            far: 100.,
+        }
+    }
+
+    fn to_ndc(camera: &Camera, point: Vector3) -> Vector3 {
+        let (x, y, z, w) = transform_point_homogeneous(
+            (point.0, point.1, point.2, 1.),
+            &camera.view_projection_matrix(),
+        );
+        (x / w, y / w, z / w)
+    }
+
+    #[test]
+    fn near_and_far_planes_go_to_the_ends_of_the_depth_range() {
+        let camera = camera();
+        let near = to_ndc(&camera, (0., 0., 9.));
+        let far = to_ndc(&camera, (0., 0., -90.));
+        assert!(near.0.abs() < 1e-9 && near.1.abs() < 1e-9);
+        assert!((near.2 + 1.).abs() < 1e-9 && (far.2 - 1.).abs() < 1e-9);
+    }
+
+    #[test]
+    fn the_field_of_view_reaches_the_edges() {
+        //at a distance of 10 a 90 degrees field of view is 10 up and, with an aspect of 2, 20 to the side
+        let camera = camera();
+        let corner = to_ndc(&camera, (20., 10., 0.));
+        assert!((corner.0 - 1.).abs() < 1e-9 && (corner.1 - 1.).abs() < 1e-9);
+    }
+
+    #[test]
+    fn cameras_without_a_view_or_a_projection_have_a_problem() {
+        assert!(camera().problem().is_none());
+        let broken: [fn(&mut Camera); 9] = [
+            |camera| camera.fov = 0.,
+            |camera| camera.fov = 180.,
+            |camera| camera.fov = f64::NAN,
+            |camera| camera.aspect = 0.,
+            |camera| camera.near = 0.,
+            |camera| camera.far = camera.near,
+            |camera| camera.target = camera.eye,
+            |camera| camera.up = (0., 0., -3.),
+            |camera| camera.up = (0., 0., 0.),
+        ];
+        for change in broken {
+            let mut camera = camera();
+            change(&mut camera);
+            assert!(camera.problem().is_some());
+        }
+    }
+}
@@ -2,11 +2,17 @@ pub type Rgba = [u8; 4];
 pub type Point = (i32, i32);
 pub type Image = Vec<Vec<Rgba>>;
 pub type Edge = (Point, Point);
+//vertices of a polygon, in order
+pub type Polygon = Vec<Point>;
 //3d point in homogeneous coordinates
 pub type HomogeneousPoint = (f64, f64, f64, f64);
 // pub type Border = (i32, i32, i32, i32);
 pub type HomogeneousEdge = (HomogeneousPoint, HomogeneousPoint);
 pub type ObjectPoint = (HomogeneousPoint, Rgba);
+//a point of a surface with its color and the unit normal of the surface at it
+pub type SurfacePoint = (HomogeneousPoint, Rgba, Vector3);
+//3d vector or point in cartesian coordinates
+pub type Vector3 = (f64, f64, f64);
 pub struct Border {
     pub top: i32,
     pub bottom: i32,
@@ -0,0 +1,223 @@
+use crate::camera::Camera;
+use crate::common::{HomogeneousPoint, Image, Point, Rgba, Vector3};
+use crate::mesh::Mesh;
+use crate::operations::{
+    clip_edge_homogeneous, clip_polygon_homogeneous, draw_line_in_image,
+    transform_point_homogeneous, view_setup,
+};
+use crate::vector::lerp;
+use crate::viewport::{ndc_to_device, WindowViewport};
+
+//length in pixels of the dashes of hidden edges, and of the gaps between them
+const DASH_LENGTH: f64 = 4.;
+//how much deeper than a face an edge has to be to be hidden by it, in normalized device coordinates.
+//it keeps the edges that lie on a face visible
+const DEPTH_EPSILON: f64 = 1e-7;
+
+fn to_ndc(point: HomogeneousPoint) -> Vector3 {
+    (point.0 / point.3, point.1 / point.3, point.2 / point.3)
+}
+
+fn cross_2d(a: (f64, f64), b: (f64, f64)) -> f64 {
+    a.0 * b.1 - a.1 * b.0
+}
+
+fn difference_2d(a: Vector3, b: Vector3) -> (f64, f64) {
+    (a.0 - b.0, a.1 - b.1)
+}
+
+//the part of the segment, as an interval of its parameter, that is behind the triangle.
+//both are in normalized device coordinates, where a smaller z is closer to the viewer
+fn hidden_interval(p0: Vector3, p1: Vector3, triangle: &[Vector3; 3]) -> Option<(f64, f64)> {
+    let [a, b, c] = *triangle;
+    let area = cross_2d(difference_2d(b, a), difference_2d(c, a));
+    if area.abs() < f64::EPSILON {
+        return None;
+    }
+    let corners = match area > 0. {
+        true => [a, b, c],
+        false => [a, c, b],
+    };
+
+    //the part of the segment inside the triangle, the same way cyrus-beck clips against a convex window
+    let direction = difference_2d(p1, p0);
+    let (mut t_enter, mut t_leave): (f64, f64) = (0., 1.);
+    for i in 0..3 {
+        let (e0, e1) = (corners[i], corners[(i + 1) % 3]);
+        let edge = difference_2d(e1, e0);
+        let inside = cross_2d(edge, difference_2d(p0, e0));
+        let change = cross_2d(edge, direction);
+        if change.abs() < f64::EPSILON {
+            if inside < 0. {
+                return None;
+            }
+            continue;
+        }
+        let t = -inside / change;
+        match change > 0. {
+            true => t_enter = t_enter.max(t),
+            false => t_leave = t_leave.min(t),
+        }
+    }
+    if t_leave - t_enter <= f64::EPSILON {
+        return None;
+    }
+
+    //how far behind the plane of the triangle the segment is, it changes linearly along the segment
+    let depth_behind = |t: f64| {
+        let point = lerp(p0, p1, t);
+        let w_b = cross_2d(difference_2d(point, a), difference_2d(c, a)) / area;
+        let w_c = cross_2d(difference_2d(b, a), difference_2d(point, a)) / area;
+        let triangle_depth = a.2 + w_b * (b.2 - a.2) + w_c * (c.2 - a.2);
+        point.2 - triangle_depth
+    };
+    let (d_enter, d_leave) = (depth_behind(t_enter), depth_behind(t_leave));
+    match (d_enter > DEPTH_EPSILON, d_leave > DEPTH_EPSILON) {
+        (true, true) => Some((t_enter, t_leave)),
+        (false, false) => None,
+        (enter_hidden, _) => {
+            let t = t_enter + (t_leave - t_enter) * (DEPTH_EPSILON - d_enter) / (d_leave - d_enter);
+            match enter_hidden {
+                true => Some((t_enter, t)),
+                false => Some((t, t_leave)),
+            }
+        }
+    }
+}
+
+//split the segment in the pieces between the hidden intervals, each one marked as hidden or not
+fn split_segment(mut hidden: Vec<(f64, f64)>) -> Vec<(f64, f64, bool)> {
+    hidden.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let mut pieces: Vec<(f64, f64, bool)> = vec![];
+    let mut t: f64 = 0.;
+    for (start, end) in hidden {
+        if start > t {
+            pieces.push((t, start, false));
+        }
+        if end > t {
+            pieces.push((t.max(start), end, true));
+            t = end;
+        }
+    }
+    if t < 1. {
+        pieces.push((t, 1., false));
+    }
+    pieces
+}
+
+fn draw_dashed_line(image: &mut Image, p0: &Point, p1: &Point, color: &Rgba) {
+    let length = ((p1.0 - p0.0) as f64).hypot((p1.1 - p0.1) as f64);
+    let dashes = (length / DASH_LENGTH).ceil().max(1.) as usize;
+    let at = |i: usize| {
+        let t = i.min(dashes) as f64 / dashes as f64;
+        (
+            p0.0 + ((p1.0 - p0.0) as f64 * t).round() as i32,
+            p0.1 + ((p1.1 - p0.1) as f64 * t).round() as i32,
+        )
+    };
+    for i in (0..dashes).step_by(2) {
+        draw_line_in_image(image, &at(i), &at(i + 1), color);
+    }
+}
+
+//draw the edges of a mesh with the parts hidden behind its faces removed, or dashed when dashed_hidden is set.
+//each edge is tested against every face that does not contain it, after both are clipped to the view volume
+pub fn draw_mesh_hidden_lines(
+    image: Image,
+    mesh: &Mesh,
+    dashed_hidden: bool,
+    camera: Option<&Camera>,
+    viewport: Option<&WindowViewport>,
+) -> Image {
+    let mut new_image: Image = image.clone();
+    let (matrix, area) = view_setup(image[0].len(), image.len(), camera, viewport);
+    let clip_vertices: Vec<HomogeneousPoint> = mesh
+        .vertices
+        .iter()
+        .map(|vertex| transform_point_homogeneous(*vertex, &matrix))
+        .collect();
+
+    //the faces clipped to the view volume, split in triangles, with the vertices of the face they come from
+    let mut occluders: Vec<([Vector3; 3], [usize; 3])> = vec![];
+    for (a, b, c) in mesh.faces.iter() {
+        let polygon =
+            clip_polygon_homogeneous(&[clip_vertices[*a], clip_vertices[*b], clip_vertices[*c]]);
+        let ndc: Vec<Vector3> = polygon.into_iter().map(to_ndc).collect();
+        for i in 1..ndc.len().saturating_sub(1) {
+            occluders.push(([ndc[0], ndc[i], ndc[i + 1]], [*a, *b, *c]));
+        }
+    }
+
+    for (a, b) in mesh.edge_indexes() {
+        let (c0, c1) = match clip_edge_homogeneous(&(clip_vertices[a], clip_vertices[b])) {
+            Some(edge) => edge,
+            None => continue,
+        };
+        let (p0, p1) = (to_ndc(c0), to_ndc(c1));
+        let hidden: Vec<(f64, f64)> = occluders
+            .iter()
+            .filter(|(_, face)| !(face.contains(&a) && face.contains(&b)))
+            .filter_map(|(triangle, _)| hidden_interval(p0, p1, triangle))
+            .collect();
+
+        let color = mesh.colors[a];
+        for (start, end, is_hidden) in split_segment(hidden) {
+            if is_hidden && !dashed_hidden {
+                continue;
+            }
+            let (start, end) = (lerp(p0, p1, start), lerp(p0, p1, end));
+            let start = ndc_to_device(start.0, start.1, &area);
+            let end = ndc_to_device(end.0, end.1, &area);
+            match is_hidden {
+                true => draw_dashed_line(&mut new_image, &start, &end, &color),
+                false => draw_line_in_image(&mut new_image, &start, &end, &color),
+            }
+        }
+    }
+    new_image
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::viewport::WindowViewport;
+
+    #[test]
+    fn segment_behind_a_triangle_is_hidden_where_it_crosses_it() {
+        let triangle = [(-1., -1., 0.), (1., -1., 0.), (0., 1., 0.)];
+        let behind = hidden_interval((-2., 0., 0.5), (2., 0., 0.5), &triangle).unwrap();
+        assert!((behind.0 - 0.375).abs() < 1e-9 && (behind.1 - 0.625).abs() < 1e-9);
+        assert!(hidden_interval((-2., 0., -0.5), (2., 0., -0.5), &triangle).is_none());
+    }
+
+    #[test]
+    fn split_segment_covers_overlapping_intervals_once() {
+        let pieces = split_segment(vec![(0.5, 0.7), (0.2, 0.6)]);
+        assert_eq!(
+            pieces,
+            vec![
+                (0., 0.2, false),
+                (0.2, 0.6, true),
+                (0.6, 0.7, true),
+                (0.7, 1., false)
+            ]
+        );
+    }
+
+    #[test]
+    fn viewport_bigger_than_the_image_does_not_panic() {
+        let mesh = Mesh::from_triangles(
+            &[[(-1., -1., 0., 1.), (1., -1., 0., 1.), (0., 1., 0., 1.)]],
+            [255, 0, 0, 255],
+        );
+        let viewport = WindowViewport {
+            window: (-0.5, -0.5, 0.5, 0.5),
+            viewport: Some(((-100, -100), (150, 150))),
+            keep_aspect: false,
+        };
+        for dashed_hidden in [false, true] {
+            let image = vec![vec![[255; 4]; 50]; 50];
+            draw_mesh_hidden_lines(image, &mesh, dashed_hidden, None, Some(&viewport));
+        }
+    }
+}
@@ -1,11 +1,42 @@
+use bsp::BspTree;
+use camera::Camera;
 use common::HomogeneousPoint;
 use common::ObjectPoint;
+use common::Vector3;
+use light::Light;
+use mesh::Mesh;
+use pathtrace::PathTracer;
+use pyo3::exceptions::PyValueError;
 use pyo3::prelude::*;
 use pyo3::wrap_pyfunction;
+use raster::{DepthBuffer, DepthColormap};
+use raytrace::{Material, Scene};
+use rotation::{check_axis, Quaternion};
+use transform::Transform;
+use viewport::WindowViewport;
 
+mod bsp;
+mod camera;
 mod common;
+mod hidden_lines;
+mod light;
+mod mesh;
+mod obj;
 mod operations;
-use common::{Edge, HomogeneousEdge, Image, Point, Rgba};
+mod painter;
+mod pathtrace;
+mod ply;
+mod projection;
+mod raster;
+mod raytrace;
+mod reflection;
+mod rotation;
+mod scanline;
+mod stl;
+mod transform;
+mod vector;
+mod viewport;
+use common::{Edge, HomogeneousEdge, Image, Point, Polygon, Rgba};
 
 // #[pyfunction]
 // fn draw_line(image: Image) -> PyResult<Image> {
@@ -63,45 +94,209 @@ fn cohen_sutherland(
 }
 
 #[pyfunction]
+fn cyrus_beck(image: Image, p0: Point, p1: Point, color: Rgba, window: Polygon) -> PyResult<Image> {
+    Ok(operations::cyrus_beck(image, p0, p1, color, window))
+}
+
+#[pyfunction]
+fn clip_edges_homogeneous(edges: Vec<HomogeneousEdge>) -> PyResult<Vec<HomogeneousEdge>> {
+    Ok(edges
+        .iter()
+        .filter_map(operations::clip_edge_homogeneous)
+        .collect())
+}
+
+#[pyfunction]
+fn clip_polygon_homogeneous(polygon: Vec<HomogeneousPoint>) -> PyResult<Vec<HomogeneousPoint>> {
+    Ok(operations::clip_polygon_homogeneous(&polygon))
+}
+
+//a pivot is either a point or one of the names "origin", "centroid" and "bbox"
+#[derive(FromPyObject)]
+enum PivotArgument {
+    Point(Vector3),
+    Name(String),
+}
+
+fn pivot_from_argument(pivot: Option<PivotArgument>) -> PyResult<operations::Pivot> {
+    match pivot {
+        None => Ok(operations::Pivot::Origin),
+        Some(PivotArgument::Point((x, y, z))) => Ok(operations::Pivot::Point((x, y, z, 1.))),
+        Some(PivotArgument::Name(name)) => match name.as_str() {
+            "origin" => Ok(operations::Pivot::Origin),
+            "centroid" => Ok(operations::Pivot::Centroid),
+            "bbox" => Ok(operations::Pivot::BoundingBox),
+            _ => Err(PyValueError::new_err(format!(
+                "the pivot must be a point, 'origin', 'centroid' or 'bbox', got '{}'",
+                name
+            ))),
+        },
+    }
+}
+
+//the object functions take a list of edges or a mesh, and give back the same kind they were given
+#[derive(FromPyObject)]
+enum ObjectArgument {
+    Mesh(Mesh),
+    Edges(Vec<HomogeneousEdge>),
+}
+
+impl From<ObjectArgument> for operations::Object {
+    fn from(object: ObjectArgument) -> Self {
+        match object {
+            ObjectArgument::Mesh(mesh) => operations::Object::Mesh(mesh),
+            ObjectArgument::Edges(edges) => operations::Object::Edges(edges),
+        }
+    }
+}
+
+impl IntoPy<PyObject> for operations::Object {
+    fn into_py(self, py: Python) -> PyObject {
+        match self {
+            operations::Object::Mesh(mesh) => mesh.into_py(py),
+            operations::Object::Edges(edges) => edges.into_py(py),
+        }
+    }
+}
+
+#[pyfunction(camera = "None", viewport = "None")]
 fn translate_object(
     image: Image,
-    edges: Vec<HomogeneousEdge>,
+    object: ObjectArgument,
     axis: [f64; 3],
-) -> PyResult<(Image, Vec<HomogeneousEdge>)> {
-    Ok(operations::translate_object(image, edges, axis))
+    camera: Option<Camera>,
+    viewport: Option<WindowViewport>,
+) -> PyResult<(Image, operations::Object)> {
+    Ok(operations::translate_object(
+        image,
+        object.into(),
+        axis,
+        camera.as_ref(),
+        viewport.as_ref(),
+    ))
 }
 
-#[pyfunction]
+#[pyfunction(pivot = "None", camera = "None", viewport = "None")]
 fn scale_object(
     image: Image,
-    edges: Vec<HomogeneousEdge>,
+    object: ObjectArgument,
     scale: [f64; 4],
-) -> PyResult<(Image, Vec<HomogeneousEdge>)> {
-    Ok(operations::scale_object(image, edges, scale))
+    pivot: Option<PivotArgument>,
+    camera: Option<Camera>,
+    viewport: Option<WindowViewport>,
+) -> PyResult<(Image, operations::Object)> {
+    Ok(operations::scale_object(
+        image,
+        object.into(),
+        scale,
+        &pivot_from_argument(pivot)?,
+        camera.as_ref(),
+        viewport.as_ref(),
+    ))
 }
 
-#[pyfunction]
+#[pyfunction(pivot = "None", camera = "None", viewport = "None")]
 fn shear_object(
     image: Image,
-    edges: Vec<HomogeneousEdge>,
+    object: ObjectArgument,
     matrix: [[f64; 4]; 4],
-) -> PyResult<(Image, Vec<HomogeneousEdge>)> {
-    Ok(operations::shear_object(image, edges, matrix))
+    pivot: Option<PivotArgument>,
+    camera: Option<Camera>,
+    viewport: Option<WindowViewport>,
+) -> PyResult<(Image, operations::Object)> {
+    Ok(operations::shear_object(
+        image,
+        object.into(),
+        matrix,
+        &pivot_from_argument(pivot)?,
+        camera.as_ref(),
+        viewport.as_ref(),
+    ))
 }
 
-#[pyfunction]
+#[pyfunction(camera = "None", viewport = "None")]
 fn rotate_object(
     image: Image,
-    edges: Vec<HomogeneousEdge>,
+    object: ObjectArgument,
     degrees: f64,
     axis: char,
     center: bool,
-) -> PyResult<(Image, Vec<HomogeneousEdge>)> {
+    camera: Option<Camera>,
+    viewport: Option<WindowViewport>,
+) -> PyResult<(Image, operations::Object)> {
+    check_axis(axis)?;
     Ok(operations::rotate_object(
-        image, edges, degrees, axis, center,
+        image,
+        object.into(),
+        degrees,
+        axis,
+        center,
+        camera.as_ref(),
+        viewport.as_ref(),
+    ))
+}
+
+#[pyfunction(camera = "None", viewport = "None")]
+fn transform_object(
+    image: Image,
+    object: ObjectArgument,
+    transform: Transform,
+    camera: Option<Camera>,
+    viewport: Option<WindowViewport>,
+) -> PyResult<(Image, operations::Object)> {
+    Ok(operations::transform_object(
+        image,
+        object.into(),
+        transform.matrix,
+        camera.as_ref(),
+        viewport.as_ref(),
     ))
 }
 
+#[pyfunction(camera = "None", viewport = "None")]
+fn reflect_object(
+    image: Image,
+    object: ObjectArgument,
+    point: Vector3,
+    normal: Vector3,
+    camera: Option<Camera>,
+    viewport: Option<WindowViewport>,
+) -> PyResult<(Image, operations::Object)> {
+    if normal == (0., 0., 0.) {
+        return Err(PyValueError::new_err("the plane normal can not be zero"));
+    }
+    Ok(operations::transform_object(
+        image,
+        object.into(),
+        reflection::reflection_matrix(point, normal),
+        camera.as_ref(),
+        viewport.as_ref(),
+    ))
+}
+
+#[pyfunction(alpha = "None", beta = "None", viewport = "None")]
+fn parallel_projection(
+    image: Image,
+    object: ObjectArgument,
+    projection: &str,
+    alpha: Option<f64>,
+    beta: Option<f64>,
+    viewport: Option<WindowViewport>,
+) -> PyResult<Image> {
+    match projection::parallel_projection_matrix(projection, alpha, beta) {
+        Some(matrix) => Ok(operations::parallel_projection_object(
+            image,
+            &object.into(),
+            matrix,
+            viewport.as_ref(),
+        )),
+        None => Err(PyValueError::new_err(format!(
+            "unknown parallel projection: {}",
+            projection
+        ))),
+    }
+}
+
 //in order to use print a 3d object in screen, you first need to get an object using the get_object method
 //then you print the object with the method print objects_in_screen
 #[pyfunction]
@@ -109,9 +304,20 @@ fn get_object(object_type: u16) -> PyResult<Vec<ObjectPoint>> {
     Ok(operations::get_object(object_type))
 }
 
-#[pyfunction]
-fn print_objects_in_screen(image: Image, points: Vec<ObjectPoint>) -> PyResult<Image> {
-    Ok(operations::print_objects_in_screen(image, points, true))
+#[pyfunction(camera = "None", viewport = "None")]
+fn print_objects_in_screen(
+    image: Image,
+    points: Vec<ObjectPoint>,
+    camera: Option<Camera>,
+    viewport: Option<WindowViewport>,
+) -> PyResult<Image> {
+    Ok(operations::print_objects_in_screen(
+        image,
+        points,
+        true,
+        camera.as_ref(),
+        viewport.as_ref(),
+    ))
 }
 
 //the same applies when using the translation/rotation functions
@@ -131,6 +337,7 @@ fn rotate_3d_object(
     axis: char,
     around_itself: bool,
 ) -> PyResult<Vec<ObjectPoint>> {
+    check_axis(axis)?;
     Ok(operations::rotate_3d_object(
         &points,
         degrees,
@@ -139,6 +346,235 @@ fn rotate_3d_object(
     ))
 }
 
+#[pyfunction]
+fn get_mesh(object_type: u16) -> PyResult<Mesh> {
+    Ok(operations::get_mesh(object_type))
+}
+
+#[pyfunction]
+fn transform_mesh(mesh: Mesh, transform: Transform) -> PyResult<Mesh> {
+    Ok(mesh.transformed(&transform.matrix))
+}
+
+#[pyfunction(camera = "None", viewport = "None")]
+fn draw_mesh_wireframe(
+    image: Image,
+    mesh: Mesh,
+    camera: Option<Camera>,
+    viewport: Option<WindowViewport>,
+) -> PyResult<Image> {
+    Ok(operations::draw_mesh_wireframe(
+        image,
+        &mesh,
+        camera.as_ref(),
+        viewport.as_ref(),
+    ))
+}
+
+#[pyfunction(camera = "None", viewport = "None")]
+fn draw_mesh_points(
+    image: Image,
+    mesh: Mesh,
+    camera: Option<Camera>,
+    viewport: Option<WindowViewport>,
+) -> PyResult<Image> {
+    Ok(operations::draw_mesh_points(
+        image,
+        &mesh,
+        camera.as_ref(),
+        viewport.as_ref(),
+    ))
+}
+
+#[pyfunction(camera = "None", viewport = "None")]
+fn draw_mesh_solid(
+    image: Image,
+    mesh: Mesh,
+    camera: Option<Camera>,
+    viewport: Option<WindowViewport>,
+) -> PyResult<Image> {
+    let (image, _) = raster::render_mesh(image, &mesh, camera.as_ref(), viewport.as_ref());
+    Ok(image)
+}
+
+#[pyfunction(camera = "None", viewport = "None")]
+fn render_mesh(
+    image: Image,
+    mesh: Mesh,
+    camera: Option<Camera>,
+    viewport: Option<WindowViewport>,
+) -> PyResult<(Image, DepthBuffer)> {
+    Ok(raster::render_mesh(
+        image,
+        &mesh,
+        camera.as_ref(),
+        viewport.as_ref(),
+    ))
+}
+
+#[pyfunction(camera = "None", viewport = "None")]
+fn render_points(
+    image: Image,
+    points: Vec<ObjectPoint>,
+    camera: Option<Camera>,
+    viewport: Option<WindowViewport>,
+) -> PyResult<(Image, DepthBuffer)> {
+    Ok(operations::render_points(
+        image,
+        points,
+        true,
+        camera.as_ref(),
+        viewport.as_ref(),
+    ))
+}
+
+#[pyfunction(near = "None", far = "None", colormap = "\"gray\"")]
+fn depth_to_image(
+    depth: DepthBuffer,
+    near: Option<f64>,
+    far: Option<f64>,
+    colormap: &str,
+) -> PyResult<Image> {
+    let colormap = match colormap {
+        "gray" => DepthColormap::Gray,
+        "jet" => DepthColormap::Jet,
+        _ => {
+            return Err(PyValueError::new_err(format!(
+                "unknown colormap '{}', use gray or jet",
+                colormap
+            )))
+        }
+    };
+    Ok(depth.to_image(near, far, &colormap))
+}
+
+#[pyfunction(camera = "None", viewport = "None")]
+fn draw_mesh_painter(
+    image: Image,
+    mesh: Mesh,
+    camera: Option<Camera>,
+    viewport: Option<WindowViewport>,
+) -> PyResult<Image> {
+    Ok(painter::draw_mesh_painter(
+        image,
+        &mesh,
+        camera.as_ref(),
+        viewport.as_ref(),
+    ))
+}
+
+#[pyfunction(camera = "None", viewport = "None")]
+fn draw_mesh_scanline(
+    image: Image,
+    mesh: Mesh,
+    camera: Option<Camera>,
+    viewport: Option<WindowViewport>,
+) -> PyResult<Image> {
+    Ok(scanline::draw_mesh_scanline(
+        image,
+        &mesh,
+        camera.as_ref(),
+        viewport.as_ref(),
+    ))
+}
+
+//the faces of a mesh from back to front, as the outline in pixels and the color of each one
+#[pyfunction(camera = "None", viewport = "None")]
+fn painter_polygons(
+    mesh: Mesh,
+    width: usize,
+    height: usize,
+    camera: Option<Camera>,
+    viewport: Option<WindowViewport>,
+) -> PyResult<Vec<(painter::Outline, Rgba)>> {
+    let polygons = painter::project_faces(&mesh, width, height, camera.as_ref(), viewport.as_ref());
+    Ok(painter::painter_order(polygons)
+        .iter()
+        .map(|polygon| (polygon.outline(), polygon.color))
+        .collect())
+}
+
+#[pyfunction(camera = "None", viewport = "None")]
+fn painter_svg(
+    mesh: Mesh,
+    width: usize,
+    height: usize,
+    camera: Option<Camera>,
+    viewport: Option<WindowViewport>,
+) -> PyResult<String> {
+    let polygons = painter::project_faces(&mesh, width, height, camera.as_ref(), viewport.as_ref());
+    Ok(painter::polygons_to_svg(
+        &painter::painter_order(polygons),
+        width,
+        height,
+    ))
+}
+
+#[pyfunction(dashed_hidden = "false", camera = "None", viewport = "None")]
+fn draw_mesh_hidden_lines(
+    image: Image,
+    mesh: Mesh,
+    dashed_hidden: bool,
+    camera: Option<Camera>,
+    viewport: Option<WindowViewport>,
+) -> PyResult<Image> {
+    Ok(hidden_lines::draw_mesh_hidden_lines(
+        image,
+        &mesh,
+        dashed_hidden,
+        camera.as_ref(),
+        viewport.as_ref(),
+    ))
+}
+
+#[pyfunction]
+fn load_obj(path: String) -> PyResult<Mesh> {
+    Ok(obj::read_obj(&path)?)
+}
+
+#[pyfunction]
+fn save_obj(mesh: Mesh, path: String) -> PyResult<()> {
+    Ok(obj::write_obj(&mesh, &path)?)
+}
+
+#[pyfunction]
+fn load_stl(path: String) -> PyResult<Mesh> {
+    Ok(stl::read_stl(&path)?)
+}
+
+#[pyfunction(binary = "true")]
+fn save_stl(mesh: Mesh, path: String, binary: bool) -> PyResult<()> {
+    Ok(stl::write_stl(&mesh, &path, binary)?)
+}
+
+#[pyfunction]
+fn load_ply(path: String) -> PyResult<Mesh> {
+    Ok(ply::read_ply(&path)?)
+}
+
+#[pyfunction(binary = "true")]
+fn save_ply(mesh: Mesh, path: String, binary: bool) -> PyResult<()> {
+    Ok(ply::write_ply(&mesh, &path, binary)?)
+}
+
+#[pyfunction]
+fn load_ply_points(path: String) -> PyResult<Vec<ObjectPoint>> {
+    Ok(ply::read_ply(&path)?.to_points())
+}
+
+#[pyfunction(binary = "true")]
+fn save_ply_points(points: Vec<ObjectPoint>, path: String, binary: bool) -> PyResult<()> {
+    Ok(ply::write_ply(&Mesh::from_points(&points), &path, binary)?)
+}
+
+#[pyfunction]
+fn transform_3d_object(
+    points: Vec<ObjectPoint>,
+    transform: Transform,
+) -> PyResult<Vec<ObjectPoint>> {
+    Ok(operations::transform_3d_object(&points, &transform.matrix))
+}
+
 #[pyfunction]
 fn apply_luminosity(
     image: Image,
@@ -147,14 +583,46 @@ fn apply_luminosity(
     ks_1: f64,
     kd_2: f64,
     ks_2: f64,
-    k: f64,
     ia: f64,
     ka: f64,
-    il: f64,
     n: f64,
+    lights: Vec<Light>,
 ) -> PyResult<Image> {
     Ok(operations::apply_luminosity(
-        image, model, kd_1, ks_1, kd_2, ks_2, k, ia, ka, il, n,
+        image, model, kd_1, ks_1, kd_2, ks_2, ia, ka, n, &lights,
+    ))
+}
+
+//light the colors of a mesh, model 1 has ambient and diffuse light, 2 is phong and 3 is blinn-phong
+#[pyfunction(model = "2", ambient = "0.1", observer = "(0., 0., 100.)")]
+fn illuminate_mesh(
+    mesh: &Mesh,
+    lights: Vec<Light>,
+    material: &Material,
+    model: u8,
+    ambient: f64,
+    observer: Vector3,
+) -> PyResult<Mesh> {
+    Ok(operations::illuminate_mesh(
+        mesh, model, material, ambient, observer, &lights,
+    ))
+}
+
+//trace the scene through every pixel, following reflections and refractions max_depth times at most
+#[pyfunction(max_depth = "5", camera = "None", viewport = "None")]
+fn ray_trace(
+    image: Image,
+    scene: &Scene,
+    max_depth: usize,
+    camera: Option<Camera>,
+    viewport: Option<WindowViewport>,
+) -> PyResult<Image> {
+    Ok(raytrace::ray_trace(
+        image,
+        scene,
+        max_depth,
+        camera.as_ref(),
+        viewport.as_ref(),
     ))
 }
 
@@ -168,8 +636,27 @@ fn rotate_plane_sweep(image: Image, color: Rgba) -> PyResult<Image> {
     Ok(operations::rotate_plane_sweep(image, color))
 }
 
+#[pyfunction(steps = "72")]
+fn rotate_plane_sweep_mesh(image: Image, color: Rgba, steps: usize) -> PyResult<Mesh> {
+    if steps < 3 {
+        return Err(PyValueError::new_err("the sweep needs at least 3 steps"));
+    }
+    Ok(operations::rotate_plane_sweep_mesh(&image, color, steps))
+}
+
 #[pymodule]
 fn cglib(_py: Python, m: &PyModule) -> PyResult<()> {
+    m.add_class::<Camera>()?;
+    m.add_class::<WindowViewport>()?;
+    m.add_class::<Transform>()?;
+    m.add_class::<Quaternion>()?;
+    m.add_class::<Mesh>()?;
+    m.add_class::<DepthBuffer>()?;
+    m.add_class::<BspTree>()?;
+    m.add_class::<Light>()?;
+    m.add_class::<Material>()?;
+    m.add_class::<Scene>()?;
+    m.add_class::<PathTracer>()?;
     m.add_function(wrap_pyfunction!(draw_line, m)?)?;
     m.add_function(wrap_pyfunction!(draw_line_bresenham, m)?)?;
     m.add_function(wrap_pyfunction!(draw_circle, m)?)?;
@@ -180,15 +667,46 @@ fn cglib(_py: Python, m: &PyModule) -> PyResult<()> {
     m.add_function(wrap_pyfunction!(edge_fill, m)?)?;
     m.add_function(wrap_pyfunction!(select_area, m)?)?;
     m.add_function(wrap_pyfunction!(cohen_sutherland, m)?)?;
+    m.add_function(wrap_pyfunction!(cyrus_beck, m)?)?;
+    m.add_function(wrap_pyfunction!(clip_edges_homogeneous, m)?)?;
+    m.add_function(wrap_pyfunction!(clip_polygon_homogeneous, m)?)?;
     m.add_function(wrap_pyfunction!(translate_object, m)?)?;
     m.add_function(wrap_pyfunction!(scale_object, m)?)?;
     m.add_function(wrap_pyfunction!(shear_object, m)?)?;
     m.add_function(wrap_pyfunction!(rotate_object, m)?)?;
+    m.add_function(wrap_pyfunction!(transform_object, m)?)?;
+    m.add_function(wrap_pyfunction!(reflect_object, m)?)?;
+    m.add_function(wrap_pyfunction!(parallel_projection, m)?)?;
     m.add_function(wrap_pyfunction!(get_object, m)?)?;
     m.add_function(wrap_pyfunction!(print_objects_in_screen, m)?)?;
     m.add_function(wrap_pyfunction!(translate_3d_object, m)?)?;
     m.add_function(wrap_pyfunction!(rotate_3d_object, m)?)?;
+    m.add_function(wrap_pyfunction!(transform_3d_object, m)?)?;
+    m.add_function(wrap_pyfunction!(get_mesh, m)?)?;
+    m.add_function(wrap_pyfunction!(transform_mesh, m)?)?;
+    m.add_function(wrap_pyfunction!(draw_mesh_wireframe, m)?)?;
+    m.add_function(wrap_pyfunction!(draw_mesh_points, m)?)?;
+    m.add_function(wrap_pyfunction!(draw_mesh_hidden_lines, m)?)?;
+    m.add_function(wrap_pyfunction!(draw_mesh_solid, m)?)?;
+    m.add_function(wrap_pyfunction!(render_mesh, m)?)?;
+    m.add_function(wrap_pyfunction!(render_points, m)?)?;
+    m.add_function(wrap_pyfunction!(depth_to_image, m)?)?;
+    m.add_function(wrap_pyfunction!(draw_mesh_painter, m)?)?;
+    m.add_function(wrap_pyfunction!(painter_polygons, m)?)?;
+    m.add_function(wrap_pyfunction!(painter_svg, m)?)?;
+    m.add_function(wrap_pyfunction!(draw_mesh_scanline, m)?)?;
+    m.add_function(wrap_pyfunction!(load_obj, m)?)?;
+    m.add_function(wrap_pyfunction!(save_obj, m)?)?;
+    m.add_function(wrap_pyfunction!(load_stl, m)?)?;
+    m.add_function(wrap_pyfunction!(save_stl, m)?)?;
+    m.add_function(wrap_pyfunction!(load_ply, m)?)?;
+    m.add_function(wrap_pyfunction!(save_ply, m)?)?;
+    m.add_function(wrap_pyfunction!(load_ply_points, m)?)?;
+    m.add_function(wrap_pyfunction!(save_ply_points, m)?)?;
     m.add_function(wrap_pyfunction!(apply_luminosity, m)?)?;
+    m.add_function(wrap_pyfunction!(illuminate_mesh, m)?)?;
+    m.add_function(wrap_pyfunction!(ray_trace, m)?)?;
     m.add_function(wrap_pyfunction!(rotate_plane_sweep, m)?)?;
+    m.add_function(wrap_pyfunction!(rotate_plane_sweep_mesh, m)?)?;
     Ok(())
 }
@@ -0,0 +1,283 @@
+use crate::common::Vector3;
+use crate::raytrace::Color;
+use crate::vector::{dot, length, normalize, scale, subtract};
+
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum LightKind {
+    //gives off light in every direction from its position
+    Point,
+    //so far away its light comes along the same direction to every point, with no attenuation
+    Directional,
+    //a point light that only lights a cone around its direction
+    Spot,
+}
+
+//a light of the illumination models, with an intensity for each of the red, green and blue channels.
+//the light of point and spot lights is divided by constant + linear * d + quadratic * d^2, with d the
+//distance to the lit point. a spot light lights fully inside its inner angle, and fades out to nothing
+//at its outer angle, the faster the higher its falloff
+#[pyclass]
+#[derive(Clone)]
+pub struct Light {
+    pub kind: LightKind,
+    #[pyo3(get, set)]
+    pub position: Vector3,
+    //where the light goes, for directional and spot lights
+    #[pyo3(get)]
+    pub direction: Vector3,
+    #[pyo3(get, set)]
+    pub intensity: Color,
+    //from the direction of the light to the edge of the cone, in degrees
+    #[pyo3(get)]
+    pub inner_angle: f64,
+    #[pyo3(get)]
+    pub outer_angle: f64,
+    #[pyo3(get)]
+    pub falloff: f64,
+    //constant, linear and quadratic
+    #[pyo3(get)]
+    pub attenuation: (f64, f64, f64),
+}
+
+impl Light {
+    pub fn point(position: Vector3, intensity: Color, attenuation: (f64, f64, f64)) -> Light {
+        Light {
+            kind: LightKind::Point,
+            position,
+            direction: (0., 0., -1.),
+            intensity,
+            inner_angle: 180.,
+            outer_angle: 180.,
+            falloff: 1.,
+            attenuation,
+        }
+    }
+
+    pub fn directional(direction: Vector3, intensity: Color) -> Light {
+        Light {
+            kind: LightKind::Directional,
+            direction,
+            ..Light::point((0., 0., 0.), intensity, (1., 0., 0.))
+        }
+    }
+
+    //the unit vector from a point to the light, and the light reaching the point along it
+    pub fn incoming(&self, point: Vector3) -> (Vector3, Color) {
+        if self.kind == LightKind::Directional {
+            return (scale(normalize(self.direction), -1.), self.intensity);
+        }
+        let offset = subtract(self.position, point);
+        let d = length(offset);
+        let to_light = normalize(offset);
+        let (constant, linear, quadratic) = self.attenuation;
+        let mut factor = 1. / (constant + linear * d + quadratic * d * d);
+        if self.kind == LightKind::Spot {
+            factor *= self.cone(-dot(normalize(self.direction), to_light));
+        }
+        (to_light, self.intensity.map(|channel| channel * factor))
+    }
+
+    //how far a point is from the light, directional lights are infinitely far from everything
+    pub fn distance(&self, point: Vector3) -> f64 {
+        match self.kind {
+            LightKind::Directional => f64::INFINITY,
+            _ => length(subtract(self.position, point)),
+        }
+    }
+
+    //how much of the light of a spot goes out at an angle from its direction with the given cosine
+    fn cone(&self, cosine: f64) -> f64 {
+        let (inner, outer) = (
+            self.inner_angle.to_radians().cos(),
+            self.outer_angle.to_radians().cos(),
+        );
+        if cosine >= inner {
+            1.
+        } else if cosine <= outer {
+            0.
+        } else {
+            ((cosine - outer) / (inner - outer)).powf(self.falloff)
+        }
+    }
+
+    fn set_cone(&mut self, inner_angle: f64, outer_angle: f64, falloff: f64) -> PyResult<()> {
+        if self.kind != LightKind::Spot {
+            return Err(PyValueError::new_err("only spot lights have a cone"));
+        }
+        check_cone(inner_angle, outer_angle, falloff)?;
+        (self.inner_angle, self.outer_angle, self.falloff) = (inner_angle, outer_angle, falloff);
+        Ok(())
+    }
+}
+
+//the checks are written so that nan values fail them too
+fn check_direction(direction: Vector3) -> PyResult<()> {
+    if length(direction).is_nan() || length(direction) < f64::EPSILON {
+        return Err(PyValueError::new_err("the direction can not be zero"));
+    }
+    Ok(())
+}
+
+fn check_cone(inner_angle: f64, outer_angle: f64, falloff: f64) -> PyResult<()> {
+    if !(0. <= inner_angle && inner_angle < outer_angle && outer_angle <= 90.) {
+        return Err(PyValueError::new_err(
+            "the angles must go 0 <= inner < outer <= 90",
+        ));
+    }
+    if falloff.is_nan() || falloff < 0. {
+        return Err(PyValueError::new_err("the falloff can not be negative"));
+    }
+    Ok(())
+}
+
+fn check_attenuation(attenuation: (f64, f64, f64)) -> PyResult<()> {
+    let (constant, linear, quadratic) = attenuation;
+    if !(constant >= 0. && linear >= 0. && quadratic >= 0. && constant + linear + quadratic > 0.) {
+        return Err(PyValueError::new_err(
+            "the attenuation must be positive and not all zero",
+        ));
+    }
+    Ok(())
+}
+
+#[pymethods]
+impl Light {
+    #[staticmethod]
+    #[pyo3(name = "point")]
+    #[args(intensity = "[1., 1., 1.]", attenuation = "(1., 0., 0.)")]
+    fn py_point(
+        position: Vector3,
+        intensity: Color,
+        attenuation: (f64, f64, f64),
+    ) -> PyResult<Light> {
+        check_attenuation(attenuation)?;
+        Ok(Light::point(position, intensity, attenuation))
+    }
+
+    #[staticmethod]
+    #[pyo3(name = "directional")]
+    #[args(intensity = "[1., 1., 1.]")]
+    fn py_directional(direction: Vector3, intensity: Color) -> PyResult<Light> {
+        check_direction(direction)?;
+        Ok(Light::directional(direction, intensity))
+    }
+
+    #[staticmethod]
+    #[args(
+        intensity = "[1., 1., 1.]",
+        inner_angle = "20.",
+        outer_angle = "30.",
+        falloff = "1.",
+        attenuation = "(1., 0., 0.)"
+    )]
+    fn spot(
+        position: Vector3,
+        direction: Vector3,
+        intensity: Color,
+        inner_angle: f64,
+        outer_angle: f64,
+        falloff: f64,
+        attenuation: (f64, f64, f64),
+    ) -> PyResult<Light> {
+        check_direction(direction)?;
+        check_cone(inner_angle, outer_angle, falloff)?;
+        check_attenuation(attenuation)?;
+        Ok(Light {
+            kind: LightKind::Spot,
+            position,
+            direction,
+            intensity,
+            inner_angle,
+            outer_angle,
+            falloff,
+            attenuation,
+        })
+    }
+
+    #[setter]
+    fn set_direction(&mut self, direction: Vector3) -> PyResult<()> {
+        check_direction(direction)?;
+        self.direction = direction;
+        Ok(())
+    }
+
+    #[setter]
+    fn set_attenuation(&mut self, attenuation: (f64, f64, f64)) -> PyResult<()> {
+        check_attenuation(attenuation)?;
+        self.attenuation = attenuation;
+        Ok(())
+    }
+
+    //the cone only means something for spot lights
+    #[setter]
+    fn set_inner_angle(&mut self, inner_angle: f64) -> PyResult<()> {
+        self.set_cone(inner_angle, self.outer_angle, self.falloff)
+    }
+
+    #[setter]
+    fn set_outer_angle(&mut self, outer_angle: f64) -> PyResult<()> {
+        self.set_cone(self.inner_angle, outer_angle, self.falloff)
+    }
+
+    #[setter]
+    fn set_falloff(&mut self, falloff: f64) -> PyResult<()> {
+        self.set_cone(self.inner_angle, self.outer_angle, falloff)
+    }
+
+    #[getter]
+    fn kind(&self) -> &str {
+        match self.kind {
+            LightKind::Point => "point",
+            LightKind::Directional => "directional",
+            LightKind::Spot => "spot",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spot() -> Light {
+        Light {
+            kind: LightKind::Spot,
+            direction: (0., 0., -1.),
+            inner_angle: 20.,
+            outer_angle: 40.,
+            ..Light::point((0., 0., 10.), [1., 1., 1.], (1., 0., 0.))
+        }
+    }
+
+    #[test]
+    fn point_lights_fade_with_the_distance() {
+        let light = Light::point((0., 0., 10.), [1., 0.5, 0.], (1., 0.1, 0.01));
+        let (to_light, intensity) = light.incoming((0., 0., 0.));
+        assert_eq!(to_light, (0., 0., 1.));
+        //1 / (1 + 0.1 * 10 + 0.01 * 100)
+        assert!((intensity[0] - 1. / 3.).abs() < 1e-12);
+        assert!((intensity[1] - 1. / 6.).abs() < 1e-12);
+        assert_eq!(intensity[2], 0.);
+    }
+
+    #[test]
+    fn directional_lights_come_from_the_opposite_of_their_direction() {
+        let light = Light::directional((0., -2., 0.), [0.5; 3]);
+        assert_eq!(light.incoming((100., 0., 7.)), ((0., 1., 0.), [0.5; 3]));
+    }
+
+    #[test]
+    fn spot_lights_fade_between_their_angles() {
+        let light = spot();
+        let at_angle = |degrees: f64| {
+            let x = 10. * degrees.to_radians().tan();
+            light.incoming((x, 0., 0.)).1[0]
+        };
+        assert!((at_angle(10.) - 1.).abs() < 1e-12);
+        assert_eq!(at_angle(45.), 0.);
+        let middle = at_angle(30.);
+        assert!(middle > 0. && middle < 1.);
+    }
+}
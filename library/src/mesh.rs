@@ -0,0 +1,582 @@
+use crate::common::{HomogeneousEdge, HomogeneousPoint, ObjectPoint, Rgba, SurfacePoint, Vector3};
+use crate::operations::viewer_point;
+use crate::transform::{invert_matrix, Transform};
+use crate::vector::{add, cross, dot, normalize, subtract};
+
+use ndarray::{arr1, ArrayBase, Dim, OwnedRepr};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use std::collections::{BTreeSet, HashMap};
+
+//color used for vertices that come from a wireframe, the same one the wireframes are drawn with
+pub const WIREFRAME_COLOR: Rgba = [0, 0, 0, 255];
+
+//an indexed mesh. the vertices are shared by the edges and the triangle faces, which refer to them by index.
+//colors has one entry per vertex, normals and uvs are either empty or also have one entry per vertex.
+//groups names sets of faces, by their indexes.
+//faces are counter-clockwise seen from their front, so their normals point out of closed solids, and with
+//cull_back_faces set the renderers skip the faces turned away from the viewer
+#[pyclass]
+#[derive(Clone, Default)]
+pub struct Mesh {
+    #[pyo3(get)]
+    pub vertices: Vec<HomogeneousPoint>,
+    #[pyo3(get)]
+    pub colors: Vec<Rgba>,
+    #[pyo3(get)]
+    pub normals: Vec<Vector3>,
+    #[pyo3(get)]
+    pub edges: Vec<(usize, usize)>,
+    #[pyo3(get)]
+    pub faces: Vec<(usize, usize, usize)>,
+    #[pyo3(get)]
+    pub uvs: Vec<(f64, f64)>,
+    #[pyo3(get)]
+    pub groups: Vec<(String, Vec<usize>)>,
+    #[pyo3(get, set)]
+    pub cull_back_faces: bool,
+}
+
+//key used to merge vertices with the same coordinates, -0 and 0 are the same vertex
+pub fn vertex_key(point: &HomogeneousPoint) -> (u64, u64, u64, u64) {
+    (
+        (point.0 + 0.).to_bits(),
+        (point.1 + 0.).to_bits(),
+        (point.2 + 0.).to_bits(),
+        (point.3 + 0.).to_bits(),
+    )
+}
+
+pub fn point_to_vector(point: HomogeneousPoint) -> Vector3 {
+    (point.0 / point.3, point.1 / point.3, point.2 / point.3)
+}
+
+impl Mesh {
+    //build a mesh from a wireframe, merging the endpoints the edges share
+    pub fn from_edges(edges: &[HomogeneousEdge]) -> Mesh {
+        let mut mesh = Mesh::default();
+        let mut indexes: HashMap<(u64, u64, u64, u64), usize> = HashMap::new();
+        for edge in edges.iter() {
+            let mut edge_indexes = [0; 2];
+            for (i, point) in [edge.0, edge.1].iter().enumerate() {
+                edge_indexes[i] = *indexes.entry(vertex_key(point)).or_insert_with(|| {
+                    mesh.vertices.push(*point);
+                    mesh.colors.push(WIREFRAME_COLOR);
+                    mesh.vertices.len() - 1
+                });
+            }
+            mesh.edges.push((edge_indexes[0], edge_indexes[1]));
+        }
+        mesh
+    }
+
+    //build a mesh from separate triangles, merging the corners they share
+    pub fn from_triangles(triangles: &[[HomogeneousPoint; 3]], color: Rgba) -> Mesh {
+        let mut mesh = Mesh::default();
+        let mut indexes: HashMap<(u64, u64, u64, u64), usize> = HashMap::new();
+        for triangle in triangles.iter() {
+            let mut face = [0; 3];
+            for (i, point) in triangle.iter().enumerate() {
+                face[i] = *indexes.entry(vertex_key(point)).or_insert_with(|| {
+                    mesh.vertices.push(*point);
+                    mesh.colors.push(color);
+                    mesh.vertices.len() - 1
+                });
+            }
+            mesh.faces.push((face[0], face[1], face[2]));
+        }
+        mesh.compute_normals();
+        mesh
+    }
+
+    //build a mesh from a point cloud, each point becomes a vertex
+    pub fn from_points(points: &[ObjectPoint]) -> Mesh {
+        Mesh {
+            vertices: points.iter().map(|point| point.0).collect(),
+            colors: points.iter().map(|point| point.1).collect(),
+            ..Default::default()
+        }
+    }
+
+    //the points of a surface as vertices, each one with its normal
+    pub fn from_surface_points(points: &[SurfacePoint]) -> Mesh {
+        Mesh {
+            vertices: points.iter().map(|point| point.0).collect(),
+            colors: points.iter().map(|point| point.1).collect(),
+            normals: points.iter().map(|point| point.2).collect(),
+            ..Default::default()
+        }
+    }
+
+    //the edges of the mesh together with the edges of its faces, each one once
+    pub fn edge_indexes(&self) -> Vec<(usize, usize)> {
+        if self.faces.is_empty() {
+            return self.edges.clone();
+        }
+        let mut unique: BTreeSet<(usize, usize)> = self
+            .edges
+            .iter()
+            .map(|(start, end)| (*start.min(end), *start.max(end)))
+            .collect();
+        for (a, b, c) in self.faces.iter() {
+            for (start, end) in [(*a, *b), (*b, *c), (*c, *a)] {
+                unique.insert((start.min(end), start.max(end)));
+            }
+        }
+        unique.into_iter().collect()
+    }
+
+    pub fn to_edges(&self) -> Vec<HomogeneousEdge> {
+        self.edge_indexes()
+            .iter()
+            .map(|(a, b)| (self.vertices[*a], self.vertices[*b]))
+            .collect()
+    }
+
+    pub fn to_points(&self) -> Vec<ObjectPoint> {
+        self.vertices
+            .iter()
+            .zip(self.colors.iter())
+            .map(|(vertex, color)| (*vertex, *color))
+            .collect()
+    }
+
+    //the average color of the vertices of a face
+    pub fn face_color(&self, face: &(usize, usize, usize)) -> Rgba {
+        let mut color: Rgba = [0; 4];
+        for (channel, value) in color.iter_mut().enumerate() {
+            let total: u32 = [face.0, face.1, face.2]
+                .iter()
+                .map(|index| self.colors[*index][channel] as u32)
+                .sum();
+            *value = (total as f64 / 3.).round() as u8;
+        }
+        color
+    }
+
+    //normal of each face, following the counter-clockwise winding of its vertices
+    pub fn face_normals(&self) -> Vec<Vector3> {
+        self.faces
+            .iter()
+            .map(|(a, b, c)| {
+                let p0 = point_to_vector(self.vertices[*a]);
+                let p1 = point_to_vector(self.vertices[*b]);
+                let p2 = point_to_vector(self.vertices[*c]);
+                normalize(cross(subtract(p1, p0), subtract(p2, p0)))
+            })
+            .collect()
+    }
+
+    //vertex normals as the area weighted average of the normals of the faces around each vertex
+    pub fn compute_normals(&mut self) {
+        let mut normals: Vec<Vector3> = vec![(0., 0., 0.); self.vertices.len()];
+        for (a, b, c) in self.faces.iter() {
+            let p0 = point_to_vector(self.vertices[*a]);
+            let p1 = point_to_vector(self.vertices[*b]);
+            let p2 = point_to_vector(self.vertices[*c]);
+            //the length of the cross product is twice the area of the face
+            let face_normal = cross(subtract(p1, p0), subtract(p2, p0));
+            for index in [*a, *b, *c] {
+                normals[index] = add(normals[index], face_normal);
+            }
+        }
+        self.normals = normals.into_iter().map(normalize).collect();
+    }
+
+    //apply a transformation matrix to the vertices. the normals go through the inverse transpose of the matrix,
+    //so they stay perpendicular to the surface
+    pub fn transformed(&self, matrix: &ArrayBase<OwnedRepr<f64>, Dim<[usize; 2]>>) -> Mesh {
+        let vertices = self
+            .vertices
+            .iter()
+            .map(|point| {
+                let product = arr1(&[point.0, point.1, point.2, point.3]).dot(matrix);
+                (
+                    product[0] / product[3],
+                    product[1] / product[3],
+                    product[2] / product[3],
+                    1.,
+                )
+            })
+            .collect();
+        let mut mesh = Mesh {
+            vertices,
+            ..self.clone()
+        };
+
+        if !self.normals.is_empty() {
+            match invert_matrix(matrix) {
+                Some(inverse) => {
+                    mesh.normals = self
+                        .normals
+                        .iter()
+                        .map(|normal| {
+                            //multiplying the row vector by the transpose of the inverse
+                            let product = inverse.dot(&arr1(&[normal.0, normal.1, normal.2, 0.]));
+                            normalize((product[0], product[1], product[2]))
+                        })
+                        .collect();
+                }
+                None => mesh.compute_normals(),
+            }
+        }
+        mesh
+    }
+
+    //whether each face looks towards the viewer of a clip space matrix
+    pub fn front_faces(&self, matrix: &ArrayBase<OwnedRepr<f64>, Dim<[usize; 2]>>) -> Vec<bool> {
+        let viewer = match viewer_point(matrix) {
+            Some(viewer) => viewer,
+            None => return vec![true; self.faces.len()],
+        };
+        self.faces
+            .iter()
+            .zip(self.face_normals())
+            .map(|((a, _, _), normal)| {
+                let p0 = point_to_vector(self.vertices[*a]);
+                let towards_viewer = (
+                    viewer.0 - p0.0 * viewer.3,
+                    viewer.1 - p0.1 * viewer.3,
+                    viewer.2 - p0.2 * viewer.3,
+                );
+                dot(normal, towards_viewer) > 0.
+            })
+            .collect()
+    }
+
+    //the edges to draw with a clip space matrix. when back faces are culled,
+    //the edges that only belong to back faces are left out
+    pub fn visible_edges(
+        &self,
+        matrix: &ArrayBase<OwnedRepr<f64>, Dim<[usize; 2]>>,
+    ) -> Vec<(usize, usize)> {
+        let edge_indexes = self.edge_indexes();
+        if !self.cull_back_faces || self.faces.is_empty() {
+            return edge_indexes;
+        }
+        let mut front_edges: BTreeSet<(usize, usize)> = BTreeSet::new();
+        let mut back_edges: BTreeSet<(usize, usize)> = BTreeSet::new();
+        for ((a, b, c), front) in self.faces.iter().zip(self.front_faces(matrix)) {
+            for (start, end) in [(*a, *b), (*b, *c), (*c, *a)] {
+                let edge = (start.min(end), start.max(end));
+                match front {
+                    true => front_edges.insert(edge),
+                    false => back_edges.insert(edge),
+                };
+            }
+        }
+        edge_indexes
+            .into_iter()
+            .filter(|(a, b)| {
+                let edge = (*a.min(b), *a.max(b));
+                front_edges.contains(&edge) || !back_edges.contains(&edge)
+            })
+            .collect()
+    }
+
+    //whether each vertex is shown with a clip space matrix. when back faces are culled,
+    //the vertices that only belong to back faces are hidden
+    pub fn visible_vertices(
+        &self,
+        matrix: &ArrayBase<OwnedRepr<f64>, Dim<[usize; 2]>>,
+    ) -> Vec<bool> {
+        if !self.cull_back_faces || self.faces.is_empty() {
+            return vec![true; self.vertices.len()];
+        }
+        let mut in_face = vec![false; self.vertices.len()];
+        let mut visible = vec![false; self.vertices.len()];
+        for ((a, b, c), front) in self.faces.iter().zip(self.front_faces(matrix)) {
+            for index in [*a, *b, *c] {
+                in_face[index] = true;
+                visible[index] |= front;
+            }
+        }
+        visible
+            .iter()
+            .zip(in_face.iter())
+            .map(|(visible, in_face)| *visible || !in_face)
+            .collect()
+    }
+
+    //what makes the mesh unusable by the renderers, None when its lists agree with each other
+    pub fn problem(&self) -> Option<String> {
+        let count = self.vertices.len();
+        if self.colors.len() != count {
+            return Some(format!(
+                "the mesh has {} vertices and {} colors",
+                count,
+                self.colors.len()
+            ));
+        }
+        for (name, length) in [("normals", self.normals.len()), ("uvs", self.uvs.len())] {
+            if length != 0 && length != count {
+                return Some(format!(
+                    "the mesh has {} vertices and {} {}",
+                    count, length, name
+                ));
+            }
+        }
+        let mut indexes = self
+            .edges
+            .iter()
+            .flat_map(|(a, b)| [*a, *b])
+            .chain(self.faces.iter().flat_map(|(a, b, c)| [*a, *b, *c]));
+        if let Some(index) = indexes.find(|index| *index >= count) {
+            return Some(format!(
+                "the mesh uses vertex {} but has {} vertices",
+                index, count
+            ));
+        }
+        let mut groups = self.groups.iter().flat_map(|(_, faces)| faces.iter());
+        if let Some(face) = groups.find(|face| **face >= self.faces.len()) {
+            return Some(format!(
+                "a group uses face {} but the mesh has {} faces",
+                face,
+                self.faces.len()
+            ));
+        }
+        None
+    }
+
+    //keep a change to the mesh only when the mesh is still usable after it
+    fn change(&mut self, change: impl FnOnce(&mut Mesh)) -> PyResult<()> {
+        let mut mesh = self.clone();
+        change(&mut mesh);
+        check_mesh(&mesh)?;
+        *self = mesh;
+        Ok(())
+    }
+
+    pub fn center(&self) -> HomogeneousPoint {
+        let mut center: Vector3 = (0., 0., 0.);
+        for vertex in self.vertices.iter() {
+            center = add(center, point_to_vector(*vertex));
+        }
+        let count = self.vertices.len().max(1) as f64;
+        (center.0 / count, center.1 / count, center.2 / count, 1.)
+    }
+}
+
+//meshes coming from python are checked before they reach the renderers, which index their lists directly
+pub fn check_mesh(mesh: &Mesh) -> PyResult<()> {
+    match mesh.problem() {
+        Some(problem) => Err(PyValueError::new_err(problem)),
+        None => Ok(()),
+    }
+}
+
+#[pymethods]
+impl Mesh {
+    #[new]
+    #[args(
+        vertices = "vec![]",
+        colors = "None",
+        normals = "vec![]",
+        edges = "vec![]",
+        faces = "vec![]",
+        uvs = "vec![]",
+        groups = "vec![]"
+    )]
+    fn new(
+        vertices: Vec<HomogeneousPoint>,
+        colors: Option<Vec<Rgba>>,
+        normals: Vec<Vector3>,
+        edges: Vec<(usize, usize)>,
+        faces: Vec<(usize, usize, usize)>,
+        uvs: Vec<(f64, f64)>,
+        groups: Vec<(String, Vec<usize>)>,
+    ) -> PyResult<Self> {
+        let colors = colors.unwrap_or_else(|| vec![WIREFRAME_COLOR; vertices.len()]);
+        let mesh = Mesh {
+            vertices,
+            colors,
+            normals,
+            edges,
+            faces,
+            uvs,
+            groups,
+            ..Default::default()
+        };
+        check_mesh(&mesh)?;
+        Ok(mesh)
+    }
+
+    //the lists can be replaced one at a time as long as the mesh stays usable, so changing the number of
+    //vertices needs a new mesh
+    #[setter]
+    fn set_vertices(&mut self, vertices: Vec<HomogeneousPoint>) -> PyResult<()> {
+        self.change(|mesh| mesh.vertices = vertices)
+    }
+
+    #[setter]
+    fn set_colors(&mut self, colors: Vec<Rgba>) -> PyResult<()> {
+        self.change(|mesh| mesh.colors = colors)
+    }
+
+    #[setter]
+    fn set_normals(&mut self, normals: Vec<Vector3>) -> PyResult<()> {
+        self.change(|mesh| mesh.normals = normals)
+    }
+
+    #[setter]
+    fn set_edges(&mut self, edges: Vec<(usize, usize)>) -> PyResult<()> {
+        self.change(|mesh| mesh.edges = edges)
+    }
+
+    #[setter]
+    fn set_faces(&mut self, faces: Vec<(usize, usize, usize)>) -> PyResult<()> {
+        self.change(|mesh| mesh.faces = faces)
+    }
+
+    #[setter]
+    fn set_uvs(&mut self, uvs: Vec<(f64, f64)>) -> PyResult<()> {
+        self.change(|mesh| mesh.uvs = uvs)
+    }
+
+    #[setter]
+    fn set_groups(&mut self, groups: Vec<(String, Vec<usize>)>) -> PyResult<()> {
+        self.change(|mesh| mesh.groups = groups)
+    }
+
+    #[staticmethod]
+    #[pyo3(name = "from_edges")]
+    fn py_from_edges(edges: Vec<HomogeneousEdge>) -> Mesh {
+        Mesh::from_edges(&edges)
+    }
+
+    #[staticmethod]
+    #[pyo3(name = "from_points")]
+    fn py_from_points(points: Vec<ObjectPoint>) -> Mesh {
+        Mesh::from_points(&points)
+    }
+
+    #[pyo3(name = "to_edges")]
+    fn py_to_edges(&self) -> Vec<HomogeneousEdge> {
+        self.to_edges()
+    }
+
+    #[pyo3(name = "to_points")]
+    fn py_to_points(&self) -> Vec<ObjectPoint> {
+        self.to_points()
+    }
+
+    #[pyo3(name = "compute_normals")]
+    fn py_compute_normals(&mut self) {
+        self.compute_normals();
+    }
+
+    #[pyo3(name = "center")]
+    fn py_center(&self) -> HomogeneousPoint {
+        self.center()
+    }
+
+    fn transform(&self, transform: &Transform) -> Mesh {
+        self.transformed(&transform.matrix)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camera::Camera;
+    use crate::light::Light;
+    use crate::operations::{illuminate_mesh, translate_object, view_setup, Object};
+    use crate::raytrace::Material;
+
+    fn triangle() -> Mesh {
+        Mesh::from_triangles(
+            &[[(0., 0., 0., 1.), (10., 0., 0., 1.), (0., 10., 0., 1.)]],
+            [200, 200, 200, 255],
+        )
+    }
+
+    #[test]
+    fn broken_meshes_have_a_problem() {
+        assert_eq!(triangle().problem(), None);
+        let mut mesh = triangle();
+        mesh.colors.pop();
+        assert!(mesh.problem().is_some());
+        let mut mesh = triangle();
+        mesh.edges.push((0, 3));
+        assert!(mesh.problem().is_some());
+        let mut mesh = triangle();
+        mesh.faces.push((0, 1, 5));
+        assert!(mesh.problem().is_some());
+        let mut mesh = triangle();
+        mesh.groups.push(("top".to_string(), vec![1]));
+        assert!(mesh.problem().is_some());
+    }
+
+    #[test]
+    fn object_operations_give_back_meshes() {
+        let image = vec![vec![[255, 255, 255, 255]; 50]; 50];
+        let (_, object) =
+            translate_object(image, Object::Mesh(triangle()), [1., 2., 3.], None, None);
+        let Object::Mesh(mesh) = object else {
+            panic!("a mesh went in and something else came out");
+        };
+        assert_eq!(mesh.faces, triangle().faces);
+        assert_eq!(mesh.vertices[1], (11., 2., 3., 1.));
+    }
+
+    #[test]
+    fn lit_meshes_are_brighter_facing_the_light() {
+        let material = Material::default();
+        let light = |z: f64| Light::point((0., 0., z), [1., 1., 1.], (1., 0., 0.));
+        let front = illuminate_mesh(
+            &triangle(),
+            1,
+            &material,
+            0.1,
+            (0., 0., 100.),
+            &[light(100.)],
+        );
+        let back = illuminate_mesh(
+            &triangle(),
+            1,
+            &material,
+            0.1,
+            (0., 0., 100.),
+            &[light(-100.)],
+        );
+        assert!(front.colors[0][0] > back.colors[0][0]);
+        assert_eq!(back.colors[0], [20, 20, 20, 255]);
+        assert_eq!(front.colors.len(), 3);
+    }
+
+    #[test]
+    fn faces_turned_away_from_the_viewer_are_back_faces() {
+        //counter-clockwise seen from +z, so its normal points to +z
+        let mut mesh = triangle();
+        mesh.faces.push((0, 2, 1));
+        let camera = Camera {
+            eye: (0., 0., 10.),
+            target: (0., 0., 0.),
+            up: (0., 1., 0.),
+            fov: 60.,
+            aspect: 1.,
+            near: 0.1,
+            far: 100.,
+        };
+        assert_eq!(
+            mesh.front_faces(&camera.view_projection_matrix()),
+            vec![true, false]
+        );
+        //the default view looks along +z, from the side of the smaller z
+        let (matrix, _) = view_setup(50, 50, None, None);
+        assert_eq!(mesh.front_faces(&matrix), vec![false, true]);
+    }
+
+    #[test]
+    fn culled_meshes_leave_out_the_edges_of_back_faces() {
+        let mut mesh = triangle();
+        mesh.vertices.push((10., 10., 0., 1.));
+        mesh.colors.push([0; 4]);
+        //a second face, turned the other way, shares the edge from 1 to 2
+        mesh.faces.push((1, 2, 3));
+        mesh.cull_back_faces = true;
+        let (matrix, _) = view_setup(50, 50, None, None);
+        assert_eq!(mesh.front_faces(&matrix), vec![false, true]);
+        assert_eq!(mesh.visible_edges(&matrix), vec![(1, 2), (1, 3), (2, 3)]);
+    }
+}
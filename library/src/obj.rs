@@ -0,0 +1,470 @@
+use crate::common::{HomogeneousPoint, Rgba, Vector3};
+use crate::mesh::{Mesh, WIREFRAME_COLOR};
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::{Error, ErrorKind, Result};
+use std::path::Path;
+
+//indexes of the position, uv and normal of a face vertex, and the color of its material
+type VertexKey = (usize, Option<usize>, Option<usize>, Option<Rgba>);
+
+fn invalid_data(line_number: usize, message: &str) -> Error {
+    Error::new(
+        ErrorKind::InvalidData,
+        format!("line {}: {}", line_number + 1, message),
+    )
+}
+
+fn parse_numbers(values: &[&str], line_number: usize) -> Result<Vec<f64>> {
+    values
+        .iter()
+        .map(|value| {
+            value
+                .parse::<f64>()
+                .map_err(|_| invalid_data(line_number, &format!("invalid number '{}'", value)))
+        })
+        .collect()
+}
+
+//obj indexes start at 1, and negative ones count back from the last element read so far
+fn resolve_index(value: &str, count: usize, line_number: usize) -> Result<usize> {
+    let index: i64 = value
+        .parse()
+        .map_err(|_| invalid_data(line_number, &format!("invalid index '{}'", value)))?;
+    let resolved = match index {
+        i if i > 0 => i - 1,
+        i if i < 0 => count as i64 + i,
+        _ => -1,
+    };
+    if resolved < 0 || resolved >= count as i64 {
+        return Err(invalid_data(
+            line_number,
+            &format!("index {} out of range", index),
+        ));
+    }
+    Ok(resolved as usize)
+}
+
+fn to_color(values: &[f64]) -> Rgba {
+    let channel = |value: f64| (value.clamp(0., 1.) * 255.).round() as u8;
+    [
+        channel(values[0]),
+        channel(values[1]),
+        channel(values[2]),
+        channel(*values.get(3).unwrap_or(&1.)),
+    ]
+}
+
+//diffuse colors of the materials in a mtl file, the alpha comes from the dissolve (d) or transparency (Tr) value
+pub fn parse_mtl(content: &str) -> Result<HashMap<String, Rgba>> {
+    let mut materials: HashMap<String, Rgba> = HashMap::new();
+    let mut current: Option<String> = None;
+
+    for (line_number, line) in content.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or("");
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        if tokens.is_empty() {
+            continue;
+        }
+        let values = &tokens[1..];
+        match tokens[0] {
+            "newmtl" => {
+                let name = values.join(" ");
+                materials.insert(name.clone(), WIREFRAME_COLOR);
+                current = Some(name);
+            }
+            "Kd" | "d" | "Tr" => {
+                let color = match &current {
+                    Some(name) => materials.get_mut(name).unwrap(),
+                    None => return Err(invalid_data(line_number, "property before newmtl")),
+                };
+                let numbers = parse_numbers(values, line_number)?;
+                if tokens[0] == "Kd" {
+                    if numbers.len() < 3 {
+                        return Err(invalid_data(line_number, "Kd needs three values"));
+                    }
+                    let rgb = to_color(&numbers);
+                    color[..3].copy_from_slice(&rgb[..3]);
+                } else if let Some(value) = numbers.first() {
+                    let alpha = if tokens[0] == "d" {
+                        *value
+                    } else {
+                        1. - *value
+                    };
+                    color[3] = to_color(&[0., 0., 0., alpha])[3];
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(materials)
+}
+
+//read a wavefront obj. polygons are split into triangle fans and lines become edges.
+//each combination of position, uv, normal and material becomes a vertex of the mesh,
+//colored with the diffuse color of its material or with the vertex color when the file has one.
+//the mtl files are looked up relative to the directory of the obj file
+pub fn parse_obj(content: &str, directory: Option<&Path>) -> Result<Mesh> {
+    let mut mesh = Mesh::default();
+    let mut positions: Vec<HomogeneousPoint> = vec![];
+    let mut position_colors: Vec<Option<Rgba>> = vec![];
+    let mut uvs: Vec<(f64, f64)> = vec![];
+    let mut normals: Vec<Vector3> = vec![];
+    let mut materials: HashMap<String, Rgba> = HashMap::new();
+    let mut material: Option<Rgba> = None;
+    let mut group: Option<usize> = None;
+    let mut has_uvs = false;
+    let mut has_all_normals = true;
+    let mut vertex_normals: Vec<Option<Vector3>> = vec![];
+    let mut indexes: HashMap<VertexKey, usize> = HashMap::new();
+
+    for (line_number, line) in content.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or("");
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        if tokens.is_empty() {
+            continue;
+        }
+        let values = &tokens[1..];
+        match tokens[0] {
+            "v" => {
+                let numbers = parse_numbers(values, line_number)?;
+                match numbers.len() {
+                    3 | 4 => {
+                        positions.push((
+                            numbers[0],
+                            numbers[1],
+                            numbers[2],
+                            *numbers.get(3).unwrap_or(&1.),
+                        ));
+                        position_colors.push(None);
+                    }
+                    //vertex colors, as written by meshlab and other scanners
+                    6 | 7 => {
+                        positions.push((numbers[0], numbers[1], numbers[2], 1.));
+                        position_colors.push(Some(to_color(&numbers[3..])));
+                    }
+                    _ => return Err(invalid_data(line_number, "a vertex needs three values")),
+                }
+            }
+            "vt" => {
+                let numbers = parse_numbers(values, line_number)?;
+                if numbers.is_empty() {
+                    return Err(invalid_data(line_number, "a uv needs at least one value"));
+                }
+                uvs.push((numbers[0], *numbers.get(1).unwrap_or(&0.)));
+            }
+            "vn" => {
+                let numbers = parse_numbers(values, line_number)?;
+                if numbers.len() < 3 {
+                    return Err(invalid_data(line_number, "a normal needs three values"));
+                }
+                normals.push((numbers[0], numbers[1], numbers[2]));
+            }
+            "f" | "l" => {
+                let mut element: Vec<usize> = vec![];
+                for value in values.iter() {
+                    let mut parts = value.split('/');
+                    let position =
+                        resolve_index(parts.next().unwrap_or(""), positions.len(), line_number)?;
+                    let uv = match parts.next() {
+                        Some(part) if !part.is_empty() => {
+                            Some(resolve_index(part, uvs.len(), line_number)?)
+                        }
+                        _ => None,
+                    };
+                    let normal = match parts.next() {
+                        Some(part) if !part.is_empty() => {
+                            Some(resolve_index(part, normals.len(), line_number)?)
+                        }
+                        _ => None,
+                    };
+                    let color = position_colors[position].or(material);
+                    let index =
+                        *indexes
+                            .entry((position, uv, normal, color))
+                            .or_insert_with(|| {
+                                mesh.vertices.push(positions[position]);
+                                mesh.colors.push(color.unwrap_or(WIREFRAME_COLOR));
+                                mesh.uvs.push(uv.map(|uv| uvs[uv]).unwrap_or((0., 0.)));
+                                vertex_normals.push(normal.map(|normal| normals[normal]));
+                                mesh.vertices.len() - 1
+                            });
+                    has_uvs |= uv.is_some();
+                    //lines do not need normals
+                    has_all_normals &= normal.is_some() || tokens[0] == "l";
+                    element.push(index);
+                }
+
+                if tokens[0] == "l" {
+                    if element.len() < 2 {
+                        return Err(invalid_data(line_number, "a line needs two vertices"));
+                    }
+                    for pair in element.windows(2) {
+                        mesh.edges.push((pair[0], pair[1]));
+                    }
+                    continue;
+                }
+                if element.len() < 3 {
+                    return Err(invalid_data(line_number, "a face needs three vertices"));
+                }
+                for i in 1..element.len() - 1 {
+                    if let Some(group) = group {
+                        mesh.groups[group].1.push(mesh.faces.len());
+                    }
+                    mesh.faces.push((element[0], element[i], element[i + 1]));
+                }
+            }
+            "g" | "o" => {
+                let name = match values.is_empty() {
+                    true => "default".to_string(),
+                    false => values.join(" "),
+                };
+                group = match mesh.groups.iter().position(|(other, _)| *other == name) {
+                    Some(index) => Some(index),
+                    None => {
+                        mesh.groups.push((name, vec![]));
+                        Some(mesh.groups.len() - 1)
+                    }
+                };
+            }
+            "mtllib" => {
+                for file_name in values.iter() {
+                    let path = match directory {
+                        Some(directory) => directory.join(file_name),
+                        None => Path::new(file_name).to_path_buf(),
+                    };
+                    //a missing material library only loses the colors, the geometry is still usable
+                    if let Ok(content) = fs::read_to_string(path) {
+                        materials.extend(parse_mtl(&content)?);
+                    }
+                }
+            }
+            "usemtl" => material = materials.get(&values.join(" ")).copied(),
+            _ => {}
+        }
+    }
+
+    //an obj without faces or lines is a point cloud
+    if mesh.vertices.is_empty() {
+        mesh.vertices = positions;
+        mesh.colors = position_colors
+            .into_iter()
+            .map(|color| color.unwrap_or(WIREFRAME_COLOR))
+            .collect();
+        return Ok(mesh);
+    }
+    if !has_uvs {
+        mesh.uvs = vec![];
+    }
+    if !mesh.faces.is_empty() {
+        match has_all_normals {
+            true => {
+                mesh.normals = vertex_normals
+                    .into_iter()
+                    .map(|normal| normal.unwrap_or((0., 0., 0.)))
+                    .collect()
+            }
+            false => mesh.compute_normals(),
+        }
+    }
+    mesh.groups.retain(|(_, faces)| !faces.is_empty());
+    Ok(mesh)
+}
+
+pub fn read_obj(path: &str) -> Result<Mesh> {
+    let content = fs::read_to_string(path)?;
+    parse_obj(&content, Path::new(path).parent())
+}
+
+fn material_name(color: &Rgba) -> String {
+    format!(
+        "color_{:02x}{:02x}{:02x}{:02x}",
+        color[0], color[1], color[2], color[3]
+    )
+}
+
+//write a mesh as an obj, and its colors as a mtl file next to it with the same name.
+//each face gets the material of the color of its first vertex
+pub fn write_obj(mesh: &Mesh, path: &str) -> Result<()> {
+    let path = Path::new(path);
+    let mtl_path = path.with_extension("mtl");
+    let mut obj = String::new();
+    let mut mtl = String::new();
+
+    let mtl_name = mtl_path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    obj.push_str(&format!("mtllib {}\n", mtl_name));
+    for (i, vertex) in mesh.vertices.iter().enumerate() {
+        obj.push_str(&format!(
+            "v {} {} {}",
+            vertex.0 / vertex.3,
+            vertex.1 / vertex.3,
+            vertex.2 / vertex.3
+        ));
+        //a point cloud has no faces to carry materials, so the colors go with the vertices
+        if let (true, Some(color)) = (mesh.faces.is_empty(), mesh.colors.get(i)) {
+            obj.push_str(&format!(
+                " {} {} {} {}",
+                color[0] as f64 / 255.,
+                color[1] as f64 / 255.,
+                color[2] as f64 / 255.,
+                color[3] as f64 / 255.
+            ));
+        }
+        obj.push('\n');
+    }
+    for uv in mesh.uvs.iter() {
+        obj.push_str(&format!("vt {} {}\n", uv.0, uv.1));
+    }
+    for normal in mesh.normals.iter() {
+        obj.push_str(&format!("vn {} {} {}\n", normal.0, normal.1, normal.2));
+    }
+
+    let mut written: Vec<Rgba> = vec![];
+    let mut material: Option<Rgba> = None;
+    let face_vertex = |index: usize| match (mesh.uvs.is_empty(), mesh.normals.is_empty()) {
+        (true, true) => format!("{}", index + 1),
+        (false, true) => format!("{0}/{0}", index + 1),
+        (true, false) => format!("{0}//{0}", index + 1),
+        (false, false) => format!("{0}/{0}/{0}", index + 1),
+    };
+
+    //faces outside every group go first, then each group
+    let mut grouped = vec![false; mesh.faces.len()];
+    for (_, faces) in mesh.groups.iter() {
+        for face in faces.iter() {
+            grouped[*face] = true;
+        }
+    }
+    let ungrouped: Vec<usize> = (0..mesh.faces.len())
+        .filter(|face| !grouped[*face])
+        .collect();
+    let mut sections: Vec<(Option<&String>, &Vec<usize>)> = vec![(None, &ungrouped)];
+    sections.extend(mesh.groups.iter().map(|(name, faces)| (Some(name), faces)));
+
+    //a face in several groups is only written in the first one
+    let mut written_faces = vec![false; mesh.faces.len()];
+    for (name, faces) in sections {
+        if let Some(name) = name {
+            obj.push_str(&format!("g {}\n", name));
+        }
+        for face in faces.iter() {
+            if std::mem::replace(&mut written_faces[*face], true) {
+                continue;
+            }
+            let (a, b, c) = mesh.faces[*face];
+            //the faces without a material are read back with the wireframe color
+            let color = *mesh.colors.get(a).unwrap_or(&WIREFRAME_COLOR);
+            if material != Some(color) {
+                obj.push_str(&format!("usemtl {}\n", material_name(&color)));
+                material = Some(color);
+                if !written.contains(&color) {
+                    written.push(color);
+                }
+            }
+            obj.push_str(&format!(
+                "f {} {} {}\n",
+                face_vertex(a),
+                face_vertex(b),
+                face_vertex(c)
+            ));
+        }
+    }
+    for (a, b) in mesh.edges.iter() {
+        obj.push_str(&format!("l {} {}\n", a + 1, b + 1));
+    }
+
+    for color in written.iter() {
+        mtl.push_str(&format!(
+            "newmtl {}\nKd {} {} {}\nd {}\n\n",
+            material_name(color),
+            color[0] as f64 / 255.,
+            color[1] as f64 / 255.,
+            color[2] as f64 / 255.,
+            color[3] as f64 / 255.
+        ));
+    }
+    fs::write(path, obj)?;
+    fs::write(mtl_path, mtl)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn two_triangles() -> Mesh {
+        Mesh::from_triangles(
+            &[
+                [(0., 0., 0., 1.), (1., 0., 0., 1.), (0., 1., 0., 1.)],
+                [(1., 0., 0., 1.), (1., 1., 0., 1.), (0., 1., 0., 1.)],
+            ],
+            [255, 0, 0, 255],
+        )
+    }
+
+    fn round_trip(mesh: &Mesh, name: &str) -> (Mesh, String) {
+        let path = std::env::temp_dir().join(format!("cglib_{}.obj", name));
+        write_obj(mesh, path.to_str().unwrap()).unwrap();
+        let text = fs::read_to_string(&path).unwrap();
+        let read = read_obj(path.to_str().unwrap()).unwrap();
+        fs::remove_file(&path).unwrap();
+        fs::remove_file(path.with_extension("mtl")).unwrap();
+        (read, text)
+    }
+
+    #[test]
+    fn faces_and_materials_round_trip() {
+        let mut mesh = two_triangles();
+        mesh.groups = vec![("quad".to_string(), vec![0, 1])];
+        let (read, _) = round_trip(&mesh, "round_trip");
+        assert_eq!(read.vertices, mesh.vertices);
+        assert_eq!(read.faces, mesh.faces);
+        assert_eq!(read.colors, mesh.colors);
+        assert_eq!(read.groups, mesh.groups);
+    }
+
+    #[test]
+    fn every_vertex_is_written_without_a_color() {
+        let mut mesh = Mesh::from_points(&[
+            ((0., 0., 0., 1.), [255, 0, 0, 255]),
+            ((1., 0., 0., 1.), [255, 0, 0, 255]),
+        ]);
+        mesh.colors.pop();
+        let (read, text) = round_trip(&mesh, "missing_color");
+        assert_eq!(read.vertices, mesh.vertices);
+        assert!(text.contains("v 1 0 0\n"));
+    }
+
+    #[test]
+    fn point_cloud_colors_keep_their_alpha() {
+        let mesh = Mesh::from_points(&[
+            ((0., 0., 0., 1.), [255, 0, 0, 128]),
+            ((1., 0., 0., 1.), [0, 51, 255, 0]),
+        ]);
+        let (read, _) = round_trip(&mesh, "point_cloud_alpha");
+        assert_eq!(read.vertices, mesh.vertices);
+        assert_eq!(read.colors, mesh.colors);
+    }
+
+    #[test]
+    fn faces_in_two_groups_are_written_once() {
+        let mut mesh = two_triangles();
+        mesh.groups = vec![
+            ("first".to_string(), vec![0, 1]),
+            ("second".to_string(), vec![1]),
+        ];
+        let (read, text) = round_trip(&mesh, "two_groups");
+        assert_eq!(text.matches("\nf ").count(), 2);
+        assert_eq!(read.faces, mesh.faces);
+    }
+
+    #[test]
+    fn faces_are_drawn_next_to_lines() {
+        let mesh = parse_obj("v 0 0 0\nv 1 0 0\nv 0 1 0\nv 2 2 0\nf 1 2 3\nl 3 4\n", None).unwrap();
+        assert_eq!(mesh.edges, vec![(2, 3)]);
+        assert_eq!(mesh.edge_indexes(), vec![(0, 1), (0, 2), (1, 2), (2, 3)]);
+    }
+}
@@ -1,4 +1,12 @@
+use crate::camera::Camera;
 use crate::common::*;
+use crate::light::Light;
+use crate::mesh::{point_to_vector, Mesh, WIREFRAME_COLOR};
+use crate::raster::DepthBuffer;
+use crate::raytrace::{Color, Material};
+use crate::transform::invert_matrix;
+use crate::vector::{add, dot, normalize, scale, subtract};
+use crate::viewport::{full_area, ndc_to_device, WindowViewport};
 
 use ndarray::{arr1, arr2, ArrayBase, Dim, OwnedRepr};
 #[allow(unused_imports)]
@@ -52,7 +60,7 @@ pub fn draw_line_bresenham(image: Image, p0: Point, p1: Point, color: Rgba) -> I
 }
 
 //draw a line using the bresenham algorithm
-fn draw_line_bresenham_helper(image: &mut Image, p0: &Point, p1: &Point, color: &Rgba) {
+pub fn draw_line_bresenham_helper(image: &mut Image, p0: &Point, p1: &Point, color: &Rgba) {
     let delta_x: i32 = (p1.0 - p0.0) as i32;
     let delta_y: i32 = (p1.1 - p0.1) as i32;
     // Determine the increments
@@ -99,6 +107,15 @@ fn draw_line_bresenham_helper(image: &mut Image, p0: &Point, p1: &Point, color:
     }
 }
 
+//draw the part of a line that falls inside the image, the points may be anywhere
+pub fn draw_line_in_image(image: &mut Image, p0: &Point, p1: &Point, color: &Rgba) {
+    let (x_max, y_max) = (image[0].len() as i32 - 1, image.len() as i32 - 1);
+    let screen: [Point; 4] = [(0, 0), (x_max, 0), (x_max, y_max), (0, y_max)];
+    if let Some((new_p0, new_p1)) = cyrus_beck_clip(p0, p1, &screen) {
+        draw_line_bresenham_helper(image, &new_p0, &new_p1, color);
+    }
+}
+
 fn calculate_radius(p0: Point, p1: Point) -> i32 {
     // radius for a circle from p0 to p1
     let delta_x = p1.0 - p0.0;
@@ -208,9 +225,9 @@ pub fn draw_triangle(image: Image, p0: Point, p1: Point, p2: Point, color: Rgba)
 fn homogeneous_point_to_point(h_point: HomogeneousPoint) -> Point {
     // verify if the polar coordinate is valid
     let (x, y, z, w) = h_point;
-    let normalized_h_point = match w.abs() < std::f64::EPSILON {
-        true => (x / w, y / w, z / w, 1.0),
-        false => h_point,
+    let normalized_h_point = match w.abs() < f64::EPSILON {
+        true => h_point,
+        false => (x / w, y / w, z / w, 1.0),
     };
 
     let point: Point = (normalized_h_point.0 as i32, normalized_h_point.1 as i32);
@@ -218,7 +235,7 @@ fn homogeneous_point_to_point(h_point: HomogeneousPoint) -> Point {
 }
 
 //apply the rotation matrix to the matrix
-fn scale_matrix_3d(scale: [f64; 4]) -> ArrayBase<OwnedRepr<f64>, Dim<[usize; 2]>> {
+pub fn scale_matrix_3d(scale: [f64; 4]) -> ArrayBase<OwnedRepr<f64>, Dim<[usize; 2]>> {
     // rustfmt-ignore
     let s_matrix: [[f64; 4]; 4] = [
         [scale[0], 0., 0., 0.],
@@ -229,7 +246,7 @@ fn scale_matrix_3d(scale: [f64; 4]) -> ArrayBase<OwnedRepr<f64>, Dim<[usize; 2]>
     arr2(&s_matrix)
 }
 
-fn translation_matrix_3d(
+pub fn translation_matrix_3d(
     x_translation: f64,
     y_translation: f64,
     z_translation: f64,
@@ -245,7 +262,7 @@ fn translation_matrix_3d(
     translation_matrix
 }
 
-fn calculate_center(edges: &Vec<HomogeneousEdge>) -> HomogeneousPoint {
+fn calculate_center(edges: &[HomogeneousEdge]) -> HomogeneousPoint {
     let center: HomogeneousPoint;
     let mut center_x: f64 = 0.;
     let mut center_y: f64 = 0.;
@@ -267,12 +284,64 @@ fn calculate_center(edges: &Vec<HomogeneousEdge>) -> HomogeneousPoint {
     center
 }
 
-//apply the rotation matrix to the matrix
-fn get_rotation_matrix_3d(
-    edges: &Vec<HomogeneousEdge>,
+//center of the box that holds all of the edges
+fn calculate_bounding_box_center(edges: &[HomogeneousEdge]) -> HomogeneousPoint {
+    let mut min = (f64::MAX, f64::MAX, f64::MAX);
+    let mut max = (f64::MIN, f64::MIN, f64::MIN);
+    for edge in edges.iter() {
+        for point in [edge.0, edge.1] {
+            min = (min.0.min(point.0), min.1.min(point.1), min.2.min(point.2));
+            max = (max.0.max(point.0), max.1.max(point.1), max.2.max(point.2));
+        }
+    }
+    (
+        (min.0 + max.0) / 2.,
+        (min.1 + max.1) / 2.,
+        (min.2 + max.2) / 2.,
+        1.,
+    )
+}
+
+//the fixed point of a scale or shear
+pub enum Pivot {
+    Origin,
+    //the average of the points of the edges, the same center used by the rotation
+    Centroid,
+    BoundingBox,
+    Point(HomogeneousPoint),
+}
+
+fn pivot_point(edges: &[HomogeneousEdge], pivot: &Pivot) -> Option<HomogeneousPoint> {
+    if edges.is_empty() {
+        return None;
+    }
+    match pivot {
+        Pivot::Origin => None,
+        Pivot::Centroid => Some(calculate_center(edges)),
+        Pivot::BoundingBox => Some(calculate_bounding_box_center(edges)),
+        Pivot::Point(point) => Some(*point),
+    }
+}
+
+//translate the matrix to the center, then apply the transformation and then translate it back to the original position
+fn around_point(
+    matrix: ArrayBase<OwnedRepr<f64>, Dim<[usize; 2]>>,
+    center: Option<HomogeneousPoint>,
+) -> ArrayBase<OwnedRepr<f64>, Dim<[usize; 2]>> {
+    match center {
+        Some(center) => translation_matrix_3d(-center.0, -center.1, -center.2)
+            .dot(&matrix)
+            .dot(&translation_matrix_3d(center.0, center.1, center.2)),
+        None => matrix,
+    }
+}
+
+//rotation matrix around one of the x, y or z axis, any other axis gives the identity, the python
+//functions check the axis first. x and z turn the same way as the axis-angle rotations, and y keeps
+//the direction it always had, which is the opposite one
+pub fn rotation_matrix_3d(
     rotation_degrees: f64,
     rotation_axis: char,
-    rotate_around_center: bool,
 ) -> ArrayBase<OwnedRepr<f64>, Dim<[usize; 2]>> {
     // rustfmt-ignore
     let mut matrix = [
@@ -309,23 +378,28 @@ fn get_rotation_matrix_3d(
             matrix[2][2] += 1.;
         }
     }
+    arr2(&matrix)
+}
+
+//apply the rotation matrix to the matrix
+fn get_rotation_matrix_3d(
+    edges: &[HomogeneousEdge],
+    rotation_degrees: f64,
+    rotation_axis: char,
+    rotate_around_center: bool,
+) -> ArrayBase<OwnedRepr<f64>, Dim<[usize; 2]>> {
+    let matrix = rotation_matrix_3d(rotation_degrees, rotation_axis);
     //rotate around the center of the image
-    let final_matrix: ArrayBase<OwnedRepr<f64>, Dim<[usize; 2]>>;
-    if rotate_around_center {
-        let center: HomogeneousPoint = calculate_center(&edges);
-        //translate the matrix to the center, the apply the rotation and then translate it back to the original position
-        let temp_matrix =
-            translation_matrix_3d(-center.0, -center.1, -center.2).dot(&arr2(&matrix));
-        final_matrix = temp_matrix.dot(&translation_matrix_3d(center.0, center.1, center.2));
-    } else {
-        final_matrix = arr2(&matrix);
-    }
-    final_matrix
+    let pivot = match rotate_around_center {
+        true => Pivot::Centroid,
+        false => Pivot::Origin,
+    };
+    around_point(matrix, pivot_point(edges, &pivot))
 }
 
 //apply the transformation matrix to the set of edges.
 fn apply_transformation(
-    edges: &Vec<HomogeneousEdge>,
+    edges: &[HomogeneousEdge],
     transformation_matrix: ArrayBase<OwnedRepr<f64>, Dim<[usize; 2]>>,
 ) -> Vec<HomogeneousEdge> {
     let mut new_edges: Vec<HomogeneousEdge> = Vec::new();
@@ -371,95 +445,353 @@ fn apply_transformation(
     new_edges
 }
 
-//project the a 3d set of points to a 2d image.
-fn project_to_2d(image: Image, new_edges: &Vec<HomogeneousEdge>) -> Image {
-    let mut new_image: Image = image.clone();
+//smallest w a clipped point may have, keeps the perspective divide away from zero
+const W_EPSILON: f64 = 1e-5;
 
-    //invert the y axis
-    let y_max = image.len() as i32 - 1;
+//half depth of the box drawn when there is no camera, in pixels
+pub const SCREEN_DEPTH: f64 = 10000.;
 
-    for edge in new_edges.iter() {
-        let mut p0 = homogeneous_point_to_point(edge.0);
-        let mut p1 = homogeneous_point_to_point(edge.1);
+//apply a matrix to a point without the perspective divide
+pub fn transform_point_homogeneous(
+    point: HomogeneousPoint,
+    matrix: &ArrayBase<OwnedRepr<f64>, Dim<[usize; 2]>>,
+) -> HomogeneousPoint {
+    let product = arr1(&[point.0, point.1, point.2, point.3]).dot(matrix);
+    (product[0], product[1], product[2], product[3])
+}
 
-        p0.1 = y_max - p0.1;
-        p1.1 = y_max - p1.1;
+//linear interpolation between two points in clip space
+fn interpolate_homogeneous(p0: HomogeneousPoint, p1: HomogeneousPoint, t: f64) -> HomogeneousPoint {
+    (
+        p0.0 + (p1.0 - p0.0) * t,
+        p0.1 + (p1.1 - p0.1) * t,
+        p0.2 + (p1.2 - p0.2) * t,
+        p0.3 + (p1.3 - p0.3) * t,
+    )
+}
 
-        let color = [0, 0, 0, 255];
+//signed distance of a clip space point to each plane of the view volume, the point is inside when all are >= 0.
+//the planes are -w <= x <= w, -w <= y <= w, -w <= z <= w and w >= W_EPSILON
+fn clip_plane_distances(point: &HomogeneousPoint) -> [f64; 7] {
+    let (x, y, z, w) = *point;
+    [w + x, w - x, w + y, w - y, w + z, w - z, w - W_EPSILON]
+}
 
-        let boundary = ((0, 0), (image[0].len() as i32, image.len() as i32));
+//clip a segment in homogeneous clip space against the view volume, before the perspective divide.
+//returns None when the whole segment is outside
+pub fn clip_edge_homogeneous(edge: &HomogeneousEdge) -> Option<HomogeneousEdge> {
+    let distances_0 = clip_plane_distances(&edge.0);
+    let distances_1 = clip_plane_distances(&edge.1);
+    let mut t_enter: f64 = 0.;
+    let mut t_leave: f64 = 1.;
+
+    for (d0, d1) in distances_0.iter().zip(distances_1.iter()) {
+        if *d0 < 0. && *d1 < 0. {
+            return None;
+        }
+        if *d0 < 0. {
+            t_enter = t_enter.max(d0 / (d0 - d1));
+        } else if *d1 < 0. {
+            t_leave = t_leave.min(d0 / (d0 - d1));
+        }
+        if t_enter > t_leave {
+            return None;
+        }
+    }
+
+    Some((
+        interpolate_homogeneous(edge.0, edge.1, t_enter),
+        interpolate_homogeneous(edge.0, edge.1, t_leave),
+    ))
+}
 
-        new_image = cohen_sutherland(new_image, p0, p1, color, boundary);
+//clip a polygon in homogeneous clip space against the view volume using sutherland-hodgman.
+//returns an empty polygon when the whole polygon is outside
+pub fn clip_polygon_homogeneous(polygon: &[HomogeneousPoint]) -> Vec<HomogeneousPoint> {
+    let mut output: Vec<HomogeneousPoint> = polygon.to_vec();
+    for plane in 0..7 {
+        if output.is_empty() {
+            break;
+        }
+        let input = output;
+        output = vec![];
+        for i in 0..input.len() {
+            let current = input[i];
+            let next = input[(i + 1) % input.len()];
+            let d_current = clip_plane_distances(&current)[plane];
+            let d_next = clip_plane_distances(&next)[plane];
+
+            if d_current >= 0. {
+                output.push(current);
+            }
+            //the edge crosses the plane, so the intersection becomes a new vertex
+            if (d_current >= 0.) != (d_next >= 0.) {
+                let t = d_current / (d_current - d_next);
+                output.push(interpolate_homogeneous(current, next, t));
+            }
+        }
+    }
+    output
+}
+
+//perspective divide followed by the mapping from normalized device coordinates to an area of the image.
+//the y axis is inverted, so a higher y is drawn closer to the top
+fn clip_to_viewport(point: HomogeneousPoint, area: &Edge) -> Point {
+    ndc_to_device(point.0 / point.3, point.1 / point.3, area)
+}
+
+//maps the box [0, width - 1] x [0, height - 1] x [-SCREEN_DEPTH, SCREEN_DEPTH] to the view volume,
+//so objects drawn in pixel units go through the same clipping as the ones seen by a camera
+fn screen_projection_matrix(
+    width: usize,
+    height: usize,
+) -> ArrayBase<OwnedRepr<f64>, Dim<[usize; 2]>> {
+    let x_max = (width.max(2) - 1) as f64;
+    let y_max = (height.max(2) - 1) as f64;
+    // rustfmt-ignore
+    let matrix: [[f64; 4]; 4] = [
+        [2. / x_max, 0., 0., 0.],
+        [0., 2. / y_max, 0., 0.],
+        [0., 0., 1. / SCREEN_DEPTH, 0.],
+        [-1., -1., 0., 1.],
+    ];
+    arr2(&matrix)
+}
+
+//the matrix that takes the points to clip space and the area of the image where they are drawn.
+//the camera sees world coordinates, a viewport without a camera maps its world window to the area,
+//and without both the points are already in pixels and the projection is orthographic
+pub fn view_setup(
+    width: usize,
+    height: usize,
+    camera: Option<&Camera>,
+    viewport: Option<&WindowViewport>,
+) -> (ArrayBase<OwnedRepr<f64>, Dim<[usize; 2]>>, Edge) {
+    match (camera, viewport) {
+        (Some(camera), Some(viewport)) => (
+            camera.view_projection_matrix(),
+            viewport.device_area(width, height, camera.aspect),
+        ),
+        (Some(camera), None) => (camera.view_projection_matrix(), full_area(width, height)),
+        (None, Some(viewport)) => (
+            viewport.window_matrix(),
+            viewport.device_area(width, height, viewport.window_aspect()),
+        ),
+        (None, None) => (
+            screen_projection_matrix(width, height),
+            full_area(width, height),
+        ),
+    }
+}
+
+//where the viewer of a clip space matrix is: what clip space takes to x = y = w = 0 on the near side.
+//it is a point for perspective projections and a direction, with w = 0, for parallel ones
+pub fn viewer_point(
+    matrix: &ArrayBase<OwnedRepr<f64>, Dim<[usize; 2]>>,
+) -> Option<HomogeneousPoint> {
+    let inverse = invert_matrix(matrix)?;
+    let viewer = arr1(&[0., 0., -1., 0.]).dot(&inverse);
+    Some((viewer[0], viewer[1], viewer[2], viewer[3]))
+}
+
+//draw a set of edges given in clip space, clipping them before the perspective divide.
+//colors has the color of each edge
+fn project_clip_space_to_2d(
+    image: Image,
+    clip_edges: &[HomogeneousEdge],
+    colors: &[Rgba],
+    area: &Edge,
+) -> Image {
+    let mut new_image: Image = image.clone();
+
+    for (edge, color) in clip_edges.iter().zip(colors.iter()) {
+        if let Some((c0, c1)) = clip_edge_homogeneous(edge) {
+            let p0 = clip_to_viewport(c0, area);
+            let p1 = clip_to_viewport(c1, area);
+            draw_line_in_image(&mut new_image, &p0, &p1, color);
+        }
     }
     new_image
 }
 
+//project the a 3d set of points to a 2d image.
+fn project_to_2d(
+    image: Image,
+    new_edges: &[HomogeneousEdge],
+    camera: Option<&Camera>,
+    viewport: Option<&WindowViewport>,
+) -> Image {
+    let (matrix, area) = view_setup(image[0].len(), image.len(), camera, viewport);
+    let clip_edges: Vec<HomogeneousEdge> = new_edges
+        .iter()
+        .map(|edge| {
+            (
+                transform_point_homogeneous(edge.0, &matrix),
+                transform_point_homogeneous(edge.1, &matrix),
+            )
+        })
+        .collect();
+    let colors = vec![WIREFRAME_COLOR; clip_edges.len()];
+    project_clip_space_to_2d(image, &clip_edges, &colors, &area)
+}
+
+//draw the edges of a mesh, or of its faces when it has no edges, each one with the color of its first vertex.
+//with back face culling the edges that only belong to faces turned away from the viewer are not drawn
+pub fn draw_mesh_wireframe(
+    image: Image,
+    mesh: &Mesh,
+    camera: Option<&Camera>,
+    viewport: Option<&WindowViewport>,
+) -> Image {
+    let (matrix, area) = view_setup(image[0].len(), image.len(), camera, viewport);
+    let clip_vertices: Vec<HomogeneousPoint> = mesh
+        .vertices
+        .iter()
+        .map(|vertex| transform_point_homogeneous(*vertex, &matrix))
+        .collect();
+    let edge_indexes = mesh.visible_edges(&matrix);
+    let clip_edges: Vec<HomogeneousEdge> = edge_indexes
+        .iter()
+        .map(|(a, b)| (clip_vertices[*a], clip_vertices[*b]))
+        .collect();
+    let colors: Vec<Rgba> = edge_indexes.iter().map(|(a, _)| mesh.colors[*a]).collect();
+    project_clip_space_to_2d(image, &clip_edges, &colors, &area)
+}
+
+//draw the vertices of a mesh as a point cloud, through the z-buffer.
+//with back face culling the vertices that only belong to faces turned away from the viewer are skipped
+pub fn draw_mesh_points(
+    image: Image,
+    mesh: &Mesh,
+    camera: Option<&Camera>,
+    viewport: Option<&WindowViewport>,
+) -> Image {
+    let (matrix, _) = view_setup(image[0].len(), image.len(), camera, viewport);
+    let points: Vec<ObjectPoint> = mesh
+        .to_points()
+        .into_iter()
+        .zip(mesh.visible_vertices(&matrix))
+        .filter(|(_, visible)| *visible)
+        .map(|(point, _)| point)
+        .collect();
+    print_objects_in_screen(image, points, true, camera, viewport)
+}
+
+//what the object operations work on, a list of edges or a mesh. a mesh is drawn as a wireframe,
+//with its colors and back face culling
+pub enum Object {
+    Edges(Vec<HomogeneousEdge>),
+    Mesh(Mesh),
+}
+
+impl Object {
+    //the edges the pivots are computed from
+    fn edges(&self) -> Vec<HomogeneousEdge> {
+        match self {
+            Object::Edges(edges) => edges.clone(),
+            Object::Mesh(mesh) => mesh.to_edges(),
+        }
+    }
+
+    fn transformed(&self, matrix: ArrayBase<OwnedRepr<f64>, Dim<[usize; 2]>>) -> Object {
+        match self {
+            Object::Edges(edges) => Object::Edges(apply_transformation(edges, matrix)),
+            Object::Mesh(mesh) => Object::Mesh(mesh.transformed(&matrix)),
+        }
+    }
+
+    fn draw(
+        &self,
+        image: Image,
+        camera: Option<&Camera>,
+        viewport: Option<&WindowViewport>,
+    ) -> Image {
+        match self {
+            Object::Edges(edges) => project_to_2d(image, edges, camera, viewport),
+            Object::Mesh(mesh) => draw_mesh_wireframe(image, mesh, camera, viewport),
+        }
+    }
+}
+
+//draw an object through a parallel projection matrix, the projection is made around the center of the object
+//so it stays at the same place of the screen. the object itself is not changed
+pub fn parallel_projection_object(
+    image: Image,
+    object: &Object,
+    projection_matrix: ArrayBase<OwnedRepr<f64>, Dim<[usize; 2]>>,
+    viewport: Option<&WindowViewport>,
+) -> Image {
+    let center: HomogeneousPoint = calculate_center(&object.edges());
+    let matrix = translation_matrix_3d(-center.0, -center.1, -center.2)
+        .dot(&projection_matrix)
+        .dot(&translation_matrix_3d(center.0, center.1, center.2));
+    object.transformed(matrix).draw(image, None, viewport)
+}
+
+//apply a finished transformation matrix to an object and draw it once
+pub fn transform_object(
+    image: Image,
+    object: Object,
+    transformation_matrix: ArrayBase<OwnedRepr<f64>, Dim<[usize; 2]>>,
+    camera: Option<&Camera>,
+    viewport: Option<&WindowViewport>,
+) -> (Image, Object) {
+    let new_object = object.transformed(transformation_matrix);
+    let new_image = new_object.draw(image, camera, viewport);
+    (new_image, new_object)
+}
+
 //rotate an object
 pub fn rotate_object(
     image: Image,
-    edges: Vec<HomogeneousEdge>,
+    object: Object,
     degrees: f64,
     axis: char,
     center: bool,
-) -> (Image, Vec<HomogeneousEdge>) {
-    let transformation_matrix = get_rotation_matrix_3d(&edges, degrees, axis, center);
-
-    //applying the transformation for each point in edged
-    let new_edges: Vec<HomogeneousEdge> = apply_transformation(&edges, transformation_matrix);
-
-    // let new_edges_clone = new_edges.clone();
-    //drawing each edge of the drawing
-    let new_image = project_to_2d(image, &new_edges);
-
-    (new_image, new_edges)
+    camera: Option<&Camera>,
+    viewport: Option<&WindowViewport>,
+) -> (Image, Object) {
+    let transformation_matrix = get_rotation_matrix_3d(&object.edges(), degrees, axis, center);
+    transform_object(image, object, transformation_matrix, camera, viewport)
 }
 
 //apply shearing to the object
 pub fn shear_object(
     image: Image,
-    edges: Vec<HomogeneousEdge>,
+    object: Object,
     matrix: [[f64; 4]; 4],
-) -> (Image, Vec<HomogeneousEdge>) {
-    let transformation_matrix = arr2(&matrix);
-
-    //applying the transformation for each point in edge
-    let new_edges: Vec<HomogeneousEdge> = apply_transformation(&edges, transformation_matrix);
-
-    //drawing each edge of the drawing
-    let new_image = project_to_2d(image, &new_edges);
-
-    (new_image, new_edges)
+    pivot: &Pivot,
+    camera: Option<&Camera>,
+    viewport: Option<&WindowViewport>,
+) -> (Image, Object) {
+    let transformation_matrix = around_point(arr2(&matrix), pivot_point(&object.edges(), pivot));
+    transform_object(image, object, transformation_matrix, camera, viewport)
 }
 
 //apply the given scales to the object
 pub fn scale_object(
     image: Image,
-    edges: Vec<HomogeneousEdge>,
+    object: Object,
     scale: [f64; 4],
-) -> (Image, Vec<HomogeneousEdge>) {
-    let transformation_matrix = scale_matrix_3d(scale);
-
-    //applying the transformation for each point in edge
-    let new_edges: Vec<HomogeneousEdge> = apply_transformation(&edges, transformation_matrix);
-    //drawing each edge of the drawing
-    let new_image = project_to_2d(image, &new_edges);
-
-    (new_image, new_edges)
+    pivot: &Pivot,
+    camera: Option<&Camera>,
+    viewport: Option<&WindowViewport>,
+) -> (Image, Object) {
+    let transformation_matrix =
+        around_point(scale_matrix_3d(scale), pivot_point(&object.edges(), pivot));
+    transform_object(image, object, transformation_matrix, camera, viewport)
 }
 
 //translate an object around the screen
 pub fn translate_object(
     image: Image,
-    edges: Vec<HomogeneousEdge>,
+    object: Object,
     axis: [f64; 3],
-) -> (Image, Vec<HomogeneousEdge>) {
+    camera: Option<&Camera>,
+    viewport: Option<&WindowViewport>,
+) -> (Image, Object) {
     let transformation_matrix = translation_matrix_3d(axis[0], axis[1], axis[2]);
-
-    //applying the transformation for each point in edge
-    let new_edges: Vec<HomogeneousEdge> = apply_transformation(&edges, transformation_matrix);
-    //drawing each edge of the drawing
-    let new_image = project_to_2d(image, &new_edges);
-    (new_image, new_edges)
+    transform_object(image, object, transformation_matrix, camera, viewport)
 }
 
 //show the selected area
@@ -641,6 +973,83 @@ pub fn cohen_sutherland(image: Image, p0: Point, p1: Point, color: Rgba, boundar
     new_image
 }
 
+//twice the signed area of a polygon, positive when the vertices are in counter-clockwise order
+fn polygon_signed_area(polygon: &[Point]) -> f64 {
+    let mut area: f64 = 0.;
+    for i in 0..polygon.len() {
+        let a = polygon[i];
+        let b = polygon[(i + 1) % polygon.len()];
+        area += a.0 as f64 * b.1 as f64 - b.0 as f64 * a.1 as f64;
+    }
+    area
+}
+
+//clip a line against a convex polygon using the cyrus-beck algorithm.
+//the window may be given in any winding order, but it must be convex
+fn cyrus_beck_clip(p0: &Point, p1: &Point, window: &[Point]) -> Option<Edge> {
+    if window.len() < 3 {
+        return None;
+    }
+    //the orientation is used to flip the normals so they always point inside the window
+    let area = polygon_signed_area(window);
+    if area == 0. {
+        return None;
+    }
+    let orientation = area.signum();
+
+    let direction = ((p1.0 - p0.0) as f64, (p1.1 - p0.1) as f64);
+    let mut t_enter: f64 = 0.;
+    let mut t_leave: f64 = 1.;
+
+    for i in 0..window.len() {
+        let a = window[i];
+        let b = window[(i + 1) % window.len()];
+        let normal = (
+            -(b.1 - a.1) as f64 * orientation,
+            (b.0 - a.0) as f64 * orientation,
+        );
+        //a point p(t) is inside the edge when normal . (p(t) - a) >= 0
+        let numerator = normal.0 * (p0.0 - a.0) as f64 + normal.1 * (p0.1 - a.1) as f64;
+        let denominator = normal.0 * direction.0 + normal.1 * direction.1;
+
+        if denominator == 0. {
+            //the line is parallel to this edge, so it is either fully inside or fully outside of it
+            if numerator < 0. {
+                return None;
+            }
+            continue;
+        }
+
+        let t = -numerator / denominator;
+        if denominator > 0. {
+            t_enter = t_enter.max(t);
+        } else {
+            t_leave = t_leave.min(t);
+        }
+        if t_enter > t_leave {
+            return None;
+        }
+    }
+
+    let point_at = |t: f64| -> Point {
+        (
+            (p0.0 as f64 + direction.0 * t).round() as i32,
+            (p0.1 as f64 + direction.1 * t).round() as i32,
+        )
+    };
+    Some((point_at(t_enter), point_at(t_leave)))
+}
+
+pub fn cyrus_beck(image: Image, p0: Point, p1: Point, color: Rgba, window: Polygon) -> Image {
+    let mut new_image: Image = image.clone();
+    if let Some((new_p0, new_p1)) = cyrus_beck_clip(&p0, &p1, &window) {
+        //the window may be bigger than the image, so the line is also clipped against the screen
+        let boundary = ((0, 0), (image[0].len() as i32, image.len() as i32));
+        new_image = cohen_sutherland(new_image, new_p0, new_p1, color, boundary);
+    }
+    new_image
+}
+
 // Code for the 2 part of the assignment
 // Code for the 2 part of the assignment
 // Code for the 2 part of the assignment
@@ -816,21 +1225,7 @@ pub fn edge_fill(image: Image, color: Rgba) -> Image {
 pub fn rotate_plane_sweep(image: Image, color: Rgba) -> Image {
     let width = image[0].len() as i32;
     let mut new_image: Image = image.clone();
-    let mut points_to_sweep: Vec<ObjectPoint> = vec![];
-    let (xl, xr) = (0, image[0].len() as i32);
-    let (yt, yb) = (0, image.len() as i32);
-
-    for y in yt..yb - 1 {
-        for x in xl..xr - 1 {
-            if new_image[y as usize][x as usize] == color {
-                let point: ObjectPoint =
-                    ((((width / 2) - x).abs() as f64, y as f64, 0., 1.), color);
-                points_to_sweep.push(point);
-                new_image[y as usize][x as usize] =
-                    [255 - color[0], 255 - color[1], 255 - color[2], 255];
-            }
-        }
-    }
+    let points_to_sweep = sweep_profile(&mut new_image, color);
 
     let mut sweeped_points = vec![];
 
@@ -844,11 +1239,94 @@ pub fn rotate_plane_sweep(image: Image, color: Rgba) -> Image {
     // Translate the object to the center of the screen
     sweeped_points = translate_3d_object(&sweeped_points, ((width / 2) as f64, 0., 0., 1.));
 
-    new_image = print_objects_in_screen(new_image, sweeped_points, false);
+    new_image = print_objects_in_screen(new_image, sweeped_points, false, None, None);
 
     new_image
 }
 
+//take the pixels of the given color as the profile to sweep, with x as the distance to the middle of the image.
+//the pixels taken are painted with the inverse color
+fn sweep_profile(image: &mut Image, color: Rgba) -> Vec<ObjectPoint> {
+    let width = image[0].len() as i32;
+    let mut profile: Vec<ObjectPoint> = vec![];
+    let (xl, xr) = (0, image[0].len() as i32);
+    let (yt, yb) = (0, image.len() as i32);
+
+    for y in yt..yb - 1 {
+        for x in xl..xr - 1 {
+            if image[y as usize][x as usize] == color {
+                let point: ObjectPoint =
+                    ((((width / 2) - x).abs() as f64, y as f64, 0., 1.), color);
+                profile.push(point);
+                image[y as usize][x as usize] =
+                    [255 - color[0], 255 - color[1], 255 - color[2], 255];
+            }
+        }
+    }
+    profile
+}
+
+//order the pixels of a profile as a path, starting from the first one and always going to the nearest pixel left.
+//pixels mirrored to the same position are kept once
+fn order_profile(profile: &[ObjectPoint]) -> Vec<ObjectPoint> {
+    let mut left: Vec<ObjectPoint> = vec![];
+    for point in profile.iter() {
+        if !left.iter().any(|other| other.0 == point.0) {
+            left.push(*point);
+        }
+    }
+    if left.is_empty() {
+        return left;
+    }
+
+    let mut path: Vec<ObjectPoint> = vec![left.remove(0)];
+    while !left.is_empty() {
+        let last = path[path.len() - 1].0;
+        let distance =
+            |point: &ObjectPoint| (point.0 .0 - last.0).powi(2) + (point.0 .1 - last.1).powi(2);
+        let mut nearest = 0;
+        for (i, point) in left.iter().enumerate() {
+            if distance(point) < distance(&left[nearest]) {
+                nearest = i;
+            }
+        }
+        path.push(left.remove(nearest));
+    }
+    path
+}
+
+//the solid swept by rotate_plane_sweep as a triangle mesh around the y axis, with one ring of the profile every
+//360 / steps degrees. neighbour pixels of the profile are joined by faces, so separate strokes stay apart
+pub fn rotate_plane_sweep_mesh(image: &Image, color: Rgba, steps: usize) -> Mesh {
+    let mut new_image: Image = image.clone();
+    let profile = order_profile(&sweep_profile(&mut new_image, color));
+    let count = profile.len();
+    let mut mesh = Mesh::default();
+
+    for step in 0..steps {
+        let matrix = rotation_matrix_3d(360. * step as f64 / steps as f64, 'y');
+        for (point, color) in transform_3d_object(&profile, &matrix) {
+            mesh.vertices.push(point);
+            mesh.colors.push(color);
+        }
+    }
+    for step in 0..steps {
+        let next = (step + 1) % steps;
+        for j in 0..count.saturating_sub(1) {
+            let (p0, p1) = (profile[j].0, profile[j + 1].0);
+            //diagonal neighbours are sqrt(2) pixels apart
+            if (p0.0 - p1.0).powi(2) + (p0.1 - p1.1).powi(2) > 2. {
+                continue;
+            }
+            let (a, b) = (step * count + j, next * count + j);
+            mesh.faces.push((a, b, b + 1));
+            mesh.faces.push((a, b + 1, a + 1));
+        }
+    }
+    mesh.compute_normals();
+    mesh
+}
+
 //apply the z-buffer to a set of points
 fn z_buffer(object: Vec<ObjectPoint>) -> HashMap<(i32, i32), ObjectPoint> {
     let mut buffered_points: HashMap<(i32, i32), ObjectPoint> = HashMap::new();
@@ -864,39 +1342,107 @@ fn z_buffer(object: Vec<ObjectPoint>) -> HashMap<(i32, i32), ObjectPoint> {
     buffered_points
 }
 
+//move each point to pixel coordinates through a clip space matrix, keeping the depth in z.
+//points outside of the view volume are discarded
+fn project_points_to_area(
+    points: &[ObjectPoint],
+    matrix: &ArrayBase<OwnedRepr<f64>, Dim<[usize; 2]>>,
+    area: &Edge,
+) -> Vec<ObjectPoint> {
+    points
+        .iter()
+        .filter_map(|(point, color)| {
+            let clip_point = transform_point_homogeneous(*point, matrix);
+            if clip_plane_distances(&clip_point).iter().any(|d| *d < 0.) {
+                return None;
+            }
+            let (x, y) = clip_to_viewport(clip_point, area);
+            Some((
+                (x as f64, y as f64, clip_point.2 / clip_point.3, 1.),
+                *color,
+            ))
+        })
+        .collect()
+}
+
 //print a 3d object in 2d
-pub fn print_objects_in_screen(image: Image, points: Vec<ObjectPoint>, invert: bool) -> Image {
+pub fn print_objects_in_screen(
+    image: Image,
+    points: Vec<ObjectPoint>,
+    invert: bool,
+    camera: Option<&Camera>,
+    viewport: Option<&WindowViewport>,
+) -> Image {
+    render_points(image, points, invert, camera, viewport).0
+}
+
+//print a 3d object in 2d and keep the depth of the pixels drawn. the depth is the z the z-buffer compares,
+//in normalized device coordinates with a camera or a viewport and the z of the points without them
+pub fn render_points(
+    image: Image,
+    points: Vec<ObjectPoint>,
+    invert: bool,
+    camera: Option<&Camera>,
+    viewport: Option<&WindowViewport>,
+) -> (Image, DepthBuffer) {
+    if camera.is_some() || viewport.is_some() {
+        //the viewport mapping already inverts the y axis
+        let (matrix, area) = view_setup(image[0].len(), image.len(), camera, viewport);
+        let projected = project_points_to_area(&points, &matrix, &area);
+        return render_points(image, projected, false, None, None);
+    }
     let mut new_image = image.clone();
     let z_buffered_objects = z_buffer(points);
     let height = image.len() as i32;
     let width: i32 = image[0].len() as i32;
+    let mut depth_buffer = DepthBuffer::new(width as usize, height as usize);
     for point in z_buffered_objects.values() {
         let new_point = homogeneous_point_to_point(point.0);
         let color = point.1;
-        if invert {
-            if new_point.0 >= 0 && new_point.0 < width && new_point.1 > 0 && new_point.1 <= height {
-                new_image[(height - new_point.1) as usize][(new_point.0) as usize] = color;
-            }
-        } else {
-            if new_point.0 >= 0 && new_point.0 < width && new_point.1 >= 0 && new_point.1 < height {
-                new_image[(new_point.1) as usize][(new_point.0) as usize] = color;
-            }
+        let pixel = match invert {
+            true => (new_point.0, height - new_point.1),
+            false => new_point,
+        };
+        if pixel.0 >= 0
+            && pixel.0 < width
+            && pixel.1 >= 0
+            && pixel.1 < height
+            && depth_buffer.test_and_set(pixel.0 as usize, pixel.1 as usize, point.0 .2 as f32)
+        {
+            new_image[pixel.1 as usize][pixel.0 as usize] = color;
         }
     }
-    new_image
+    (new_image, depth_buffer)
+}
+
+//get an type of object as a mesh
+pub fn get_mesh(object_type: u16) -> Mesh {
+    Mesh::from_surface_points(&get_surface_object(object_type))
 }
 
 //get an type of object
 pub fn get_object(object_type: u16) -> Vec<ObjectPoint> {
-    let new_object: Vec<ObjectPoint> = match object_type {
+    without_normals(get_surface_object(object_type))
+}
+
+//get an type of object, with the normal of its surface at each point
+fn get_surface_object(object_type: u16) -> Vec<SurfacePoint> {
+    let new_object: Vec<SurfacePoint> = match object_type {
         1 => get_z_buffer_objects(),
         _ => get_object_ramp(),
     };
     new_object
 }
 
+fn without_normals(points: Vec<SurfacePoint>) -> Vec<ObjectPoint> {
+    points
+        .into_iter()
+        .map(|(point, color, _)| (point, color))
+        .collect()
+}
+
 //return various objects to test in the z-buffer
-fn get_z_buffer_objects() -> Vec<ObjectPoint> {
+fn get_z_buffer_objects() -> Vec<SurfacePoint> {
     let mut rendered_objects = generate_object_1(10, 30, 20, 40, [0, 0, 255, 255]);
     rendered_objects.append(&mut generate_object_2(50, 100, 30, 80, [255, 0, 0, 255]));
     rendered_objects.append(&mut generate_object_3(
@@ -915,7 +1461,7 @@ fn get_z_buffer_objects() -> Vec<ObjectPoint> {
     ));
 
     rendered_objects.append(&mut generate_object_5(20, (0, 0), [255, 255, 255, 255]));
-    rendered_objects = translate_3d_object(&rendered_objects, (250., 250., 0., 1.));
+    rendered_objects = translate_surface_object(&rendered_objects, (250., 250., 0., 1.));
     rendered_objects
 }
 
@@ -926,8 +1472,9 @@ fn generate_object_1(
     min_y: i32,
     max_y: i32,
     color: Rgba,
-) -> Vec<ObjectPoint> {
-    let mut new_object: Vec<ObjectPoint> = vec![];
+) -> Vec<SurfacePoint> {
+    //the gradient of x^2 + y - z, turned towards +z
+    let mut new_object: Vec<SurfacePoint> = vec![];
     let delta = 0.1;
     let mut current_x = min_x as f64;
     while current_x <= max_x as f64 {
@@ -939,7 +1486,7 @@ fn generate_object_1(
                 (current_x * current_x + current_y),
                 1.,
             );
-            new_object.push((new_point, color) as ObjectPoint);
+            new_object.push((new_point, color, normalize((-2. * current_x, -1., 1.))));
             current_y += delta;
         }
         current_x += delta;
@@ -955,12 +1502,13 @@ fn generate_object_2(
     min_y: i32,
     max_y: i32,
     color: Rgba,
-) -> Vec<ObjectPoint> {
-    let mut new_object: Vec<ObjectPoint> = Vec::new();
+) -> Vec<SurfacePoint> {
+    let normal = normalize((-3., 2., 1.));
+    let mut new_object: Vec<SurfacePoint> = Vec::new();
     for x in min_x..=max_x {
         for y in min_y..=max_y {
             let new_point: HomogeneousPoint = (x.into(), y.into(), (3 * x - 2 * y + 5).into(), 1.);
-            new_object.push((new_point, color) as ObjectPoint);
+            new_object.push((new_point, color, normal));
         }
     }
     new_object
@@ -976,8 +1524,9 @@ fn generate_object_3(
     min_t: i32,
     max_t: i32,
     color: Rgba,
-) -> Vec<ObjectPoint> {
-    let mut new_object: Vec<ObjectPoint> = Vec::new();
+) -> Vec<SurfacePoint> {
+    //a cone around x = 30, y = 50, its normals point away from the axis
+    let mut new_object: Vec<SurfacePoint> = Vec::new();
     for t in min_t..=max_t {
         let mut current_a = min_a;
         loop {
@@ -987,7 +1536,11 @@ fn generate_object_3(
                 (10.0 + f64::from(t)).into(),
                 1.,
             );
-            new_object.push((new_point, color) as ObjectPoint);
+            new_object.push((
+                new_point,
+                color,
+                normalize((current_a.cos(), current_a.sin(), -1.)),
+            ));
             // current_a += 0.01;
 
             current_a += 0.1 / (t as f64 + 1.);
@@ -1009,8 +1562,8 @@ fn generate_object_4(
     min_b: f64,
     max_b: f64,
     color: Rgba,
-) -> Vec<ObjectPoint> {
-    let mut new_object: Vec<ObjectPoint> = Vec::new();
+) -> Vec<SurfacePoint> {
+    let mut new_object: Vec<SurfacePoint> = Vec::new();
     let mut current_b = min_b;
     loop {
         let mut current_a = min_a;
@@ -1021,7 +1574,15 @@ fn generate_object_4(
                 (20.0 + 30. * current_a.sin()).into(),
                 1.,
             );
-            new_object.push((new_point, color) as ObjectPoint);
+            new_object.push((
+                new_point,
+                color,
+                (
+                    current_a.cos() * current_b.cos(),
+                    current_a.cos() * current_b.sin(),
+                    current_a.sin(),
+                ),
+            ));
             current_a += 0.01;
             if current_a > max_a {
                 break;
@@ -1036,44 +1597,44 @@ fn generate_object_4(
     new_object
 }
 
-fn generate_object_5(side: i32, center: Point, color: Rgba) -> Vec<ObjectPoint> {
+fn generate_object_5(side: i32, center: Point, color: Rgba) -> Vec<SurfacePoint> {
     //create a cube with a center in the point center and a side of side
 
-    let mut new_object: Vec<ObjectPoint> = vec![];
+    let mut new_object: Vec<SurfacePoint> = vec![];
 
     let (x, y) = center;
 
     for i in -side..=side {
         for j in -side..=side {
             let new_point: HomogeneousPoint = ((x + i).into(), (y + j).into(), -side as f64, 1.);
-            new_object.push((new_point, color) as ObjectPoint);
+            new_object.push((new_point, color, (0., 0., -1.)));
         }
         for j in -side..=side {
             let new_point: HomogeneousPoint = ((x + i).into(), (y + j).into(), side as f64, 1.);
-            new_object.push((new_point, color) as ObjectPoint);
+            new_object.push((new_point, color, (0., 0., 1.)));
         }
         for j in -side..=side {
             let new_point: HomogeneousPoint = ((x + i).into(), -side as f64, (y + j).into(), 1.);
-            new_object.push((new_point, color) as ObjectPoint);
+            new_object.push((new_point, color, (0., -1., 0.)));
         }
         for j in -side..=side {
             let new_point: HomogeneousPoint = ((x + i).into(), side as f64, (y + j).into(), 1.);
-            new_object.push((new_point, color) as ObjectPoint);
+            new_object.push((new_point, color, (0., 1., 0.)));
         }
         for j in -side..=side {
             let new_point: HomogeneousPoint = (side as f64, (x + i).into(), (y + j).into(), 1.);
-            new_object.push((new_point, color) as ObjectPoint);
+            new_object.push((new_point, color, (1., 0., 0.)));
         }
         for j in -side..=side {
             let new_point: HomogeneousPoint = (-side as f64, (x + i).into(), (y + j).into(), 1.);
-            new_object.push((new_point, color) as ObjectPoint);
+            new_object.push((new_point, color, (-1., 0., 0.)));
         }
     }
     new_object
 }
 
 //calculate the center of an object
-fn calculate_center_object(points: &Vec<ObjectPoint>) -> HomogeneousPoint {
+fn calculate_center_object(points: &[ObjectPoint]) -> HomogeneousPoint {
     let center: HomogeneousPoint;
     let mut center_x: f64 = 0.;
     let mut center_y: f64 = 0.;
@@ -1094,58 +1655,18 @@ fn calculate_center_object(points: &Vec<ObjectPoint>) -> HomogeneousPoint {
 
 //this function gets a rotation matrix for a 3d object, not a mesh like the previous function
 fn get_rotation_matrix_3d_object(
-    points: &Vec<ObjectPoint>,
+    points: &[ObjectPoint],
     rotation_degrees: f64,
     rotation_axis: char,
     rotate_around_center: bool,
 ) -> ArrayBase<OwnedRepr<f64>, Dim<[usize; 2]>> {
-    // rustfmt-ignore
-    let mut matrix = [
-        [0., 0., 0., 0.],
-        [0., 0., 0., 0.],
-        [0., 0., 0., 0.],
-        [0., 0., 0., 1.],
-    ];
-    match rotation_axis {
-        'x' => {
-            matrix[0][0] += 1.;
-            matrix[1][1] += rotation_degrees.to_radians().cos();
-            matrix[1][2] += -rotation_degrees.to_radians().sin();
-            matrix[2][1] += rotation_degrees.to_radians().sin();
-            matrix[2][2] += rotation_degrees.to_radians().cos();
-        }
-        'y' => {
-            matrix[0][0] += rotation_degrees.to_radians().cos();
-            matrix[0][2] += -rotation_degrees.to_radians().sin();
-            matrix[2][0] += rotation_degrees.to_radians().sin();
-            matrix[2][2] += rotation_degrees.to_radians().cos();
-            matrix[1][1] += 1.;
-        }
-        'z' => {
-            matrix[0][0] += rotation_degrees.to_radians().cos();
-            matrix[0][1] += -rotation_degrees.to_radians().sin();
-            matrix[1][0] += rotation_degrees.to_radians().sin();
-            matrix[1][1] += rotation_degrees.to_radians().cos();
-            matrix[2][2] += 1.;
-        }
-        _ => {
-            matrix[0][0] += 1.;
-            matrix[1][1] += 1.;
-            matrix[2][2] += 1.;
-        }
-    }
+    let matrix = rotation_matrix_3d(rotation_degrees, rotation_axis);
     //rotate around the center of the image
-    let final_matrix: ArrayBase<OwnedRepr<f64>, Dim<[usize; 2]>>;
-    if rotate_around_center {
-        let center: HomogeneousPoint = calculate_center_object(&points);
-        //translate the matrix to the center, the apply the rotation and then translate it back to the original position
-        let temp_matrix =
-            translation_matrix_3d(-center.0, -center.1, -center.2).dot(&arr2(&matrix));
-        final_matrix = temp_matrix.dot(&translation_matrix_3d(center.0, center.1, center.2));
-    } else {
-        final_matrix = arr2(&matrix);
-    }
-    final_matrix
+    let center = match rotate_around_center {
+        true => Some(calculate_center_object(points)),
+        false => None,
+    };
+    around_point(matrix, center)
 }
 
 //this function rotates an 3d object, not an mesh, like the previous rotation function
@@ -1156,15 +1677,17 @@ pub fn rotate_3d_object(
     around_itself: bool,
 ) -> Vec<ObjectPoint> {
     let matrix = get_rotation_matrix_3d_object(points, degrees, axis, around_itself);
+    transform_3d_object(points, &matrix)
+}
+
+//apply a transformation matrix to each point of a 3d object
+pub fn transform_3d_object(
+    points: &[ObjectPoint],
+    matrix: &ArrayBase<OwnedRepr<f64>, Dim<[usize; 2]>>,
+) -> Vec<ObjectPoint> {
     let mut new_points: Vec<ObjectPoint> = Vec::new();
     for point in points {
-        let product = arr1(&[
-            point.0 .0 as f64,
-            point.0 .1 as f64,
-            point.0 .2 as f64,
-            point.0 .3 as f64,
-        ])
-        .dot(&matrix);
+        let product = arr1(&[point.0 .0, point.0 .1, point.0 .2, point.0 .3]).dot(matrix);
         let new_point: HomogeneousPoint = (
             product[0] / product[3],
             product[1] / product[3],
@@ -1194,8 +1717,30 @@ pub fn translate_3d_object(
     new_points
 }
 
+//move a surface, its normals do not change
+fn translate_surface_object(
+    points: &[SurfacePoint],
+    movement: HomogeneousPoint,
+) -> Vec<SurfacePoint> {
+    points
+        .iter()
+        .map(|(point, color, normal)| {
+            (
+                (
+                    point.0 + movement.0,
+                    point.1 + movement.1,
+                    point.2 + movement.2,
+                    1.,
+                ),
+                *color,
+                *normal,
+            )
+        })
+        .collect()
+}
+
 //create a ramp
-fn get_object_ramp() -> Vec<ObjectPoint> {
+fn get_object_ramp() -> Vec<SurfacePoint> {
     let mut rendered_objects =
         get_object_plane_xy((0., 0., 0., 1.), (0, 20), (0, 40), [0, 255, 0, 255]);
     rendered_objects.append(&mut get_object_plane_xz(
@@ -1234,25 +1779,26 @@ fn get_object_ramp() -> Vec<ObjectPoint> {
         (0, 40),
         [0, 0, 255, 255],
     ));
-    rendered_objects = translate_3d_object(&rendered_objects, (250., 250., 0., 1.));
+    rendered_objects = translate_surface_object(&rendered_objects, (250., 250., 0., 1.));
     rendered_objects
 }
 
-fn get_object_sphere(center: HomogeneousPoint, radius: f64, color: Rgba) -> Vec<ObjectPoint> {
+fn get_object_sphere(center: HomogeneousPoint, radius: f64, color: Rgba) -> Vec<SurfacePoint> {
     let delta_theta = 2. * PI / (10. * radius);
     let delta_phi = 2. * PI / (10. * radius);
     let mut i = 0.;
-    let mut rendered_sphere: Vec<ObjectPoint> = Vec::new();
+    let mut rendered_sphere: Vec<SurfacePoint> = Vec::new();
     while i < 2. * PI {
         let mut j = 0.;
         while j < 2. * PI {
+            let normal: Vector3 = (i.cos() * j.cos(), i.cos() * j.sin(), i.sin());
             let new_point: HomogeneousPoint = (
-                center.0 + radius * i.cos() * j.cos(),
-                center.1 + radius * i.cos() * j.sin(),
-                center.2 + radius * i.sin(),
+                center.0 + radius * normal.0,
+                center.1 + radius * normal.1,
+                center.2 + radius * normal.2,
                 center.3,
             );
-            rendered_sphere.push((new_point, color));
+            rendered_sphere.push((new_point, color, normal));
             j += delta_phi;
         }
         i += delta_theta;
@@ -1265,8 +1811,8 @@ fn get_object_plane_xy(
     range_x: Point,
     range_y: Point,
     color: Rgba,
-) -> Vec<ObjectPoint> {
-    let mut new_object: Vec<ObjectPoint> = vec![];
+) -> Vec<SurfacePoint> {
+    let mut new_object: Vec<SurfacePoint> = vec![];
     let delta = 0.3;
     let mut current_x = range_x.0 as f64;
     while current_x <= range_x.1 as f64 {
@@ -1278,7 +1824,7 @@ fn get_object_plane_xy(
                 initial_translation.2,
                 1.,
             );
-            new_object.push((new_point, color) as ObjectPoint);
+            new_object.push((new_point, color, (0., 0., 1.)));
             current_y += delta;
         }
         current_x += delta;
@@ -1292,8 +1838,8 @@ fn get_object_plane_xz(
     range_x: Point,
     range_z: Point,
     color: Rgba,
-) -> Vec<ObjectPoint> {
-    let mut new_object: Vec<ObjectPoint> = vec![];
+) -> Vec<SurfacePoint> {
+    let mut new_object: Vec<SurfacePoint> = vec![];
     let delta = 0.3;
     let mut current_x = range_x.0 as f64;
     while current_x <= range_x.1 as f64 {
@@ -1305,7 +1851,7 @@ fn get_object_plane_xz(
                 initial_translation.2 + current_z,
                 1.,
             );
-            new_object.push((new_point, color) as ObjectPoint);
+            new_object.push((new_point, color, (0., 1., 0.)));
             current_z += delta;
         }
         current_x += delta;
@@ -1319,8 +1865,8 @@ fn get_object_plane_yz(
     range_y: Point,
     range_z: Point,
     color: Rgba,
-) -> Vec<ObjectPoint> {
-    let mut new_object: Vec<ObjectPoint> = vec![];
+) -> Vec<SurfacePoint> {
+    let mut new_object: Vec<SurfacePoint> = vec![];
     let delta = 0.3;
     let mut current_y = range_y.0 as f64;
     while current_y <= range_y.1 as f64 {
@@ -1332,7 +1878,7 @@ fn get_object_plane_yz(
                 initial_translation.2 + current_z,
                 1.,
             );
-            new_object.push((new_point, color) as ObjectPoint);
+            new_object.push((new_point, color, (1., 0., 0.)));
             current_z += delta;
         }
         current_y += delta;
@@ -1347,8 +1893,8 @@ fn get_object_plane_declined(
     range_x: Point,
     range_y: Point,
     color: Rgba,
-) -> Vec<ObjectPoint> {
-    let mut new_object: Vec<ObjectPoint> = vec![];
+) -> Vec<SurfacePoint> {
+    let mut new_object: Vec<SurfacePoint> = vec![];
     let delta = 0.3;
     let mut current_x = range_x.0 as f64;
     while current_x <= range_x.1 as f64 {
@@ -1360,7 +1906,7 @@ fn get_object_plane_declined(
                 initial_translation.2 - current_x,
                 1.,
             );
-            new_object.push((new_point, color) as ObjectPoint);
+            new_object.push((new_point, color, normalize((1., 0., 1.))));
             current_y += delta;
         }
         current_x += delta;
@@ -1369,150 +1915,101 @@ fn get_object_plane_declined(
     new_object
 }
 
-fn array_norm(arr: ArrayBase<OwnedRepr<f64>, Dim<[usize; 1]>>) -> f64 {
-    let mut norm: f64 = 0.;
-    for num in arr {
-        norm += num * num;
-    }
-    norm = norm.sqrt();
-    norm
+//how the specular highlight of the phong model is computed, with the exponent that makes it sharper
+pub enum Specular {
+    //the angle between the reflection of the light and the direction to the observer
+    Phong(f64),
+    //the angle between the normal and the vector halfway between the light and the observer
+    BlinnPhong(f64),
 }
 
-fn calculate_angle(vec1: HomogeneousPoint, vec2: HomogeneousPoint) -> f64 {
-    let array1 = arr1(&[vec1.0 as f64, vec1.1 as f64, vec1.2 as f64]);
-    let array2 = arr1(&[vec2.0 as f64, vec2.1 as f64, vec2.2 as f64]);
-    let cos_delta: f64 = array1.dot(&array2) / (array_norm(array1) * array_norm(array2));
-    cos_delta
+impl Specular {
+    pub fn term(&self, normal: Vector3, to_light: Vector3, to_observer: Vector3) -> f64 {
+        match self {
+            Specular::Phong(n) => {
+                let reflected = subtract(scale(normal, 2. * dot(normal, to_light)), to_light);
+                dot(reflected, to_observer).max(0.).powf(*n)
+            }
+            Specular::BlinnPhong(n) => {
+                let halfway = normalize(add(to_light, to_observer));
+                dot(normal, halfway).max(0.).powf(*n)
+            }
+        }
+    }
 }
 
-fn calculate_distance(point_1: HomogeneousPoint, point_2: HomogeneousPoint) -> f64 {
-    let diff = arr1(&[
-        point_1.0 / point_1.3 - point_2.0 / point_2.3,
-        point_1.1 / point_1.3 - point_2.1 / point_2.3,
-        point_1.2 / point_1.3 - point_2.2 / point_2.3,
-        0.,
-    ]);
-    let distance = array_norm(diff);
-    distance
+fn scale_color(color: Rgba, illumination: Color) -> Rgba {
+    [
+        (color[0] as f64 * illumination[0]) as u8,
+        (color[1] as f64 * illumination[1]) as u8,
+        (color[2] as f64 * illumination[2]) as u8,
+        color[3],
+    ]
 }
 
+//the ambient light, and the diffuse light of each light, which depends on the angle between the normal
+//and the direction from each point to the light
 fn illumination_model_1(
-    rendered_objects: Vec<ObjectPoint>,
-    ia: f64,
-    ka: f64,
-    il: f64,
+    rendered_objects: Vec<SurfacePoint>,
+    ambient: f64,
     kd: f64,
-    lamp_pos: HomogeneousPoint,
+    lights: &[Light],
 ) -> Vec<ObjectPoint> {
     let mut illuminated_object: Vec<ObjectPoint> = vec![];
     for point in rendered_objects {
-        let normal: HomogeneousPoint = (0., 0., 1., 1.);
-        // let cosine = calculate_angle(observer_pos, normal);
-        let cosine = calculate_angle(lamp_pos, normal);
-        // let cosine = calculate_angle(observer_pos, lamp_pos);
-        let old_color = point.1;
-        let illumination: f64 = ia * ka + il * kd * cosine;
-        let new_color: Rgba = [
-            (old_color[0] as f64 * illumination) as u8,
-            (old_color[1] as f64 * illumination) as u8,
-            (old_color[2] as f64 * illumination) as u8,
-            old_color[3],
-        ];
-        illuminated_object.push((point.0, new_color));
+        let position = point_to_vector(point.0);
+        let normal = normalize(point.2);
+        let mut illumination: Color = [ambient; 3];
+        for light in lights {
+            let (to_light, intensity) = light.incoming(position);
+            let cosine = dot(normal, to_light).max(0.);
+            for (channel, value) in illumination.iter_mut().enumerate() {
+                *value += intensity[channel] * kd * cosine;
+            }
+        }
+        illuminated_object.push((point.0, scale_color(point.1, illumination)));
     }
     illuminated_object
 }
 
+//the phong model: the ambient light, and the diffuse and specular light of each light. the light, view
+//and reflection vectors are computed at each point
 fn illumination_model_2(
-    rendered_objects: Vec<ObjectPoint>,
-    ia: f64,
-    ka: f64,
-    il: f64,
+    rendered_objects: Vec<SurfacePoint>,
+    ambient: f64,
     kd: f64,
     ks: f64,
-    k: f64,
     observer_pos: HomogeneousPoint,
-    lamp_pos: HomogeneousPoint,
-    n: f64,
+    lights: &[Light],
+    specular: &Specular,
 ) -> Vec<ObjectPoint> {
+    let observer = point_to_vector(observer_pos);
     let mut illuminated_object: Vec<ObjectPoint> = vec![];
     for point in rendered_objects {
-        let normal: HomogeneousPoint = (0., 0., 1., 1.);
-        let cosine_alfa = calculate_angle(observer_pos, lamp_pos);
-        let cosine_delta = calculate_angle(lamp_pos, normal);
-
-        let old_color = point.1;
-        let d: f64 = calculate_distance(point.0, observer_pos);
-        let illumination: f64 =
-            ia * ka + il * (kd * cosine_delta + ks * cosine_alfa.powf(n)) / (d + k);
-        let new_color: Rgba = [
-            (old_color[0] as f64 * illumination) as u8,
-            (old_color[1] as f64 * illumination) as u8,
-            (old_color[2] as f64 * illumination) as u8,
-            old_color[3],
-        ];
-        illuminated_object.push((point.0, new_color));
-    }
-    illuminated_object
-}
-
-fn illumination_model_1_sphere(
-    rendered_objects: Vec<ObjectPoint>,
-    ia: f64,
-    ka: f64,
-    il: f64,
-    kd: f64,
-    lamp_pos: HomogeneousPoint,
-) -> Vec<ObjectPoint> {
-    let mut illuminated_object: Vec<ObjectPoint> = vec![];
-    for point in rendered_objects {
-        let normal: HomogeneousPoint = point.0;
-        let cosine = calculate_angle(lamp_pos, normal);
-        let old_color = point.1;
-        let illumination: f64 = ia * ka + il * kd * cosine;
-        let new_color: Rgba = [
-            (old_color[0] as f64 * illumination) as u8,
-            (old_color[1] as f64 * illumination) as u8,
-            (old_color[2] as f64 * illumination) as u8,
-            old_color[3],
-        ];
-        illuminated_object.push((point.0, new_color));
-    }
-    illuminated_object
-}
-
-fn illumination_model_2_sphere(
-    rendered_objects: Vec<ObjectPoint>,
-    ia: f64,
-    ka: f64,
-    il: f64,
-    kd: f64,
-    ks: f64,
-    k: f64,
-    observer_pos: HomogeneousPoint,
-    lamp_pos: HomogeneousPoint,
-    n: f64,
-) -> Vec<ObjectPoint> {
-    let mut illuminated_object: Vec<ObjectPoint> = vec![];
-    for point in rendered_objects {
-        let normal: HomogeneousPoint = point.0;
-        let cosine_alfa = calculate_angle(observer_pos, lamp_pos);
-        let cosine_delta = calculate_angle(lamp_pos, normal);
-        let old_color = point.1;
-        let d: f64 = calculate_distance(point.0, observer_pos);
-        let illumination: f64 =
-            ia * ka + il * (kd * cosine_delta + ks * cosine_alfa.powf(n)) / (d + k);
-        let new_color: Rgba = [
-            (old_color[0] as f64 * illumination) as u8,
-            (old_color[1] as f64 * illumination) as u8,
-            (old_color[2] as f64 * illumination) as u8,
-            old_color[3],
-        ];
-        illuminated_object.push((point.0, new_color));
+        let position = point_to_vector(point.0);
+        let normal = normalize(point.2);
+        let to_observer = normalize(subtract(observer, position));
+        let mut illumination: Color = [ambient; 3];
+        for light in lights {
+            let (to_light, intensity) = light.incoming(position);
+            let cosine_delta = dot(normal, to_light);
+            //a light behind the point does not light it
+            if cosine_delta <= 0. {
+                continue;
+            }
+            let direct = kd * cosine_delta + ks * specular.term(normal, to_light, to_observer);
+            for (channel, value) in illumination.iter_mut().enumerate() {
+                *value += intensity[channel] * direct;
+            }
+        }
+        illuminated_object.push((point.0, scale_color(point.1, illumination)));
     }
     illuminated_object
 }
 
+//light a plane and a sphere with a list of lights, adding up the light of each of them to the ambient
+//light ia * ka. model 1 has ambient and diffuse light, model 2 is phong and model 3 is blinn-phong,
+//any other model leaves the colors as they are
 pub fn apply_luminosity(
     image: Image,
     model: u8,
@@ -1520,55 +2017,253 @@ pub fn apply_luminosity(
     ks_1: f64,
     kd_2: f64,
     ks_2: f64,
-    k: f64,
     ia: f64,
     ka: f64,
-    il: f64,
     n: f64,
+    lights: &[Light],
 ) -> Image {
     let center: HomogeneousPoint = (0., 0., 0., 1.);
     let radius = 50.;
-    let lamp_pos: HomogeneousPoint = (100., 0., 100., 1.);
     let observer_pos: HomogeneousPoint = (0., 0., 100., 1.);
     let rendered_objects =
         get_object_plane_xy((0., 0., 0., 1.), (0, 100), (0, 100), [0, 0, 255, 255]);
     let rendered_sphere = get_object_sphere(center, radius, [255, 90, 255, 255]);
 
+    let ambient = ia * ka;
+    let specular = match model {
+        3 => Specular::BlinnPhong(n),
+        _ => Specular::Phong(n),
+    };
     let (mut object, mut sphere) = match model {
         1 => (
-            illumination_model_1(rendered_objects, ia, ka, il, kd_1, lamp_pos),
-            illumination_model_1_sphere(rendered_sphere, ia, ka, il, kd_2, lamp_pos),
+            illumination_model_1(rendered_objects, ambient, kd_1, lights),
+            illumination_model_1(rendered_sphere, ambient, kd_2, lights),
         ),
-        2 => (
+        2 | 3 => (
             illumination_model_2(
                 rendered_objects,
-                ia,
-                ka,
-                il,
+                ambient,
                 kd_1,
                 ks_1,
-                k,
                 observer_pos,
-                lamp_pos,
-                n,
+                lights,
+                &specular,
             ),
-            illumination_model_2_sphere(
+            illumination_model_2(
                 rendered_sphere,
-                ia,
-                ka,
-                il,
+                ambient,
                 kd_2,
                 ks_2,
-                k,
                 observer_pos,
-                lamp_pos,
-                n,
+                lights,
+                &specular,
             ),
         ),
-        _ => (rendered_objects, rendered_sphere),
+        _ => (
+            without_normals(rendered_objects),
+            without_normals(rendered_sphere),
+        ),
     };
 
     object.append(&mut sphere);
     object = translate_3d_object(&object, (250., 250., 0., 1.));
-    print_objects_in_screen(image, object, true)
+    print_objects_in_screen(image, object, true, None, None)
+}
+
+//light the vertices of a mesh with the same models as apply_luminosity, taking kd, ks and n from the
+//material. the normals are computed from the faces when the mesh has none
+pub fn illuminate_mesh(
+    mesh: &Mesh,
+    model: u8,
+    material: &Material,
+    ambient: f64,
+    observer: Vector3,
+    lights: &[Light],
+) -> Mesh {
+    let mut lit = mesh.clone();
+    if lit.normals.len() != lit.vertices.len() {
+        lit.compute_normals();
+    }
+    let points: Vec<SurfacePoint> = lit
+        .vertices
+        .iter()
+        .zip(lit.colors.iter())
+        .zip(lit.normals.iter())
+        .map(|((vertex, color), normal)| (*vertex, *color, *normal))
+        .collect();
+    let specular = match model {
+        3 => Specular::BlinnPhong(material.shininess),
+        _ => Specular::Phong(material.shininess),
+    };
+    let illuminated = match model {
+        1 => illumination_model_1(points, ambient, material.diffuse, lights),
+        2 | 3 => illumination_model_2(
+            points,
+            ambient,
+            material.diffuse,
+            material.specular,
+            (observer.0, observer.1, observer.2, 1.),
+            lights,
+            &specular,
+        ),
+        _ => return lit,
+    };
+    lit.colors = illuminated.into_iter().map(|point| point.1).collect();
+    lit
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cyrus_beck_clips_to_a_window_in_either_winding() {
+        let square = vec![(0, 0), (10, 0), (10, 10), (0, 10)];
+        let mut reversed = square.clone();
+        reversed.reverse();
+        for window in [square, reversed] {
+            assert_eq!(
+                cyrus_beck_clip(&(-5, 5), &(15, 5), &window),
+                Some(((0, 5), (10, 5)))
+            );
+            assert_eq!(
+                cyrus_beck_clip(&(2, 3), &(4, 6), &window),
+                Some(((2, 3), (4, 6)))
+            );
+            assert_eq!(cyrus_beck_clip(&(-5, -5), &(-1, 20), &window), None);
+        }
+    }
+
+    #[test]
+    fn cyrus_beck_clips_to_a_triangle() {
+        let triangle = [(0, 0), (10, 0), (0, 10)];
+        assert_eq!(
+            cyrus_beck_clip(&(-5, 2), &(20, 2), &triangle),
+            Some(((0, 2), (8, 2)))
+        );
+        //parallel to the long edge and outside of it
+        assert_eq!(cyrus_beck_clip(&(12, 0), &(0, 12), &triangle), None);
+        //a degenerate window clips everything
+        assert_eq!(
+            cyrus_beck_clip(&(1, 1), &(2, 2), &[(0, 0), (5, 5), (10, 10)]),
+            None
+        );
+    }
+
+    #[test]
+    fn homogeneous_edges_are_cut_at_the_view_volume() {
+        let edge = ((-3., 0., 0., 1.), (0.5, 0., 0., 1.));
+        assert_eq!(
+            clip_edge_homogeneous(&edge),
+            Some(((-1., 0., 0., 1.), (0.5, 0., 0., 1.)))
+        );
+        //outside on the same side, the x = w plane
+        assert_eq!(
+            clip_edge_homogeneous(&((2., 0., 0., 1.), (3., 1., 0., 1.))),
+            None
+        );
+        //behind the viewer w is negative, the part in front is kept
+        let (start, end) = clip_edge_homogeneous(&((0., 0., 0., -1.), (0., 0., 0., 1.))).unwrap();
+        assert!((start.3 - W_EPSILON).abs() < 1e-12);
+        assert_eq!(end, (0., 0., 0., 1.));
+    }
+
+    #[test]
+    fn homogeneous_polygons_gain_vertices_at_the_corners() {
+        //a triangle sticking out of the right side of the volume loses its tip
+        let triangle = [(0., -0.5, 0., 1.), (2., 0., 0., 1.), (0., 0.5, 0., 1.)];
+        let clipped = clip_polygon_homogeneous(&triangle);
+        assert_eq!(clipped.len(), 4);
+        assert!(clipped.iter().all(|point| point.0 <= point.3 + 1e-12));
+        assert!(
+            clip_polygon_homogeneous(&[(2., 0., 0., 1.), (3., 0., 0., 1.), (2., 1., 0., 1.)])
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn scaling_keeps_the_pivot_in_place() {
+        //an l shape, its centroid and the center of its bounding box are not the same point
+        let edges = vec![
+            ((0., 0., 0., 1.), (4., 0., 0., 1.)),
+            ((0., 0., 0., 1.), (0., 2., 0., 1.)),
+        ];
+        let image = vec![vec![[255; 4]; 20]; 20];
+        for (pivot, fixed) in [
+            (Pivot::Origin, (0., 0., 0., 1.)),
+            (Pivot::Centroid, (1., 0.5, 0., 1.)),
+            (Pivot::BoundingBox, (2., 1., 0., 1.)),
+            (Pivot::Point((4., 0., 0., 1.)), (4., 0., 0., 1.)),
+        ] {
+            let matrix = around_point(
+                scale_matrix_3d([2., 3., 1., 1.]),
+                pivot_point(&edges, &pivot),
+            );
+            assert_eq!(transform_point_homogeneous(fixed, &matrix), fixed);
+        }
+        let (_, scaled) = scale_object(
+            image,
+            Object::Edges(edges),
+            [2., 2., 1., 1.],
+            &Pivot::Centroid,
+            None,
+            None,
+        );
+        assert_eq!(scaled.edges()[0], ((-1., -0.5, 0., 1.), (7., -0.5, 0., 1.)));
+    }
+
+    #[test]
+    fn sphere_points_carry_their_outward_normals() {
+        let center = (10., -5., 3., 1.);
+        for (point, _, normal) in get_object_sphere(center, 4., [255; 4]) {
+            let radial = subtract(point_to_vector(point), point_to_vector(center));
+            assert!((dot(radial, normal) - 4.).abs() < 1e-9);
+            assert!((dot(normal, normal) - 1.).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn plane_lighting_uses_the_normal_and_not_the_position() {
+        let light = Light::directional((0., 0., -1.), [1., 1., 1.]);
+        let points = get_object_plane_xy((0., 0., 0., 1.), (0, 2), (0, 2), [100, 100, 100, 255]);
+        let lit = illumination_model_1(points, 0.2, 0.5, &[light]);
+        //every point faces the light the same way, wherever it is on the plane
+        assert!(lit.iter().all(|point| point.1 == [70, 70, 70, 255]));
+    }
+
+    #[test]
+    fn highlights_peak_along_the_mirror_direction() {
+        let normal = (0., 0., 1.);
+        let to_light = normalize((1., 0., 1.));
+        let mirror = normalize((-1., 0., 1.));
+        for specular in [Specular::Phong(8.), Specular::BlinnPhong(8.)] {
+            assert!((specular.term(normal, to_light, mirror) - 1.).abs() < 1e-12);
+            assert!(specular.term(normal, to_light, normal) < 1.);
+            assert_eq!(specular.term(normal, to_light, (0., 0., -1.)), 0.);
+        }
+        //blinn-phong falls off slower than phong for the same exponent
+        let blinn = Specular::BlinnPhong(8.).term(normal, to_light, normal);
+        assert!(blinn > Specular::Phong(8.).term(normal, to_light, normal));
+    }
+
+    #[test]
+    fn phong_lights_only_the_side_facing_the_light() {
+        let light = Light::point((0., 0., 50.), [1., 1., 1.], (1., 0., 0.));
+        let points: Vec<SurfacePoint> = vec![
+            ((0., 0., 0., 1.), [100, 100, 100, 255], (0., 0., 1.)),
+            ((0., 0., 0., 1.), [100, 100, 100, 255], (0., 0., -1.)),
+        ];
+        let lit = illumination_model_2(
+            points,
+            0.2,
+            0.5,
+            0.3,
+            (0., 0., 50., 1.),
+            &[light],
+            &Specular::Phong(4.),
+        );
+        //diffuse and a full highlight in front, only the ambient light behind
+        assert_eq!(lit[0].1, [100, 100, 100, 255]);
+        assert_eq!(lit[1].1, [20, 20, 20, 255]);
+    }
 }
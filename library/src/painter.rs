@@ -0,0 +1,438 @@
+use crate::camera::Camera;
+use crate::common::{Edge, HomogeneousPoint, Image, Rgba, Vector3};
+use crate::mesh::Mesh;
+use crate::operations::{clip_polygon_homogeneous, transform_point_homogeneous, view_setup};
+use crate::vector::{dot, length, lerp, scale};
+use crate::viewport::{ndc_to_device_exact, WindowViewport};
+
+use ndarray::{ArrayBase, Dim, OwnedRepr};
+use std::cmp::Ordering;
+
+//the corners of a polygon in pixels
+pub type Outline = Vec<(f64, f64)>;
+
+//distance to a plane under which a point is taken as lying on it
+const PLANE_EPSILON: f64 = 1e-9;
+
+//a flat convex polygon with a single color. the painter's algorithm uses them after the projection, in pixels
+//with the depth in z, where a smaller z is closer to the viewer. the projection keeps planes as planes,
+//so the polygons can be compared and split in that space as well as in the world
+#[derive(Clone)]
+pub struct DepthPolygon {
+    pub points: Vec<Vector3>,
+    pub color: Rgba,
+    normal: Vector3,
+    offset: f64,
+    //set when the polygon was moved before another one, moving it again means the polygons overlap cyclically
+    moved: bool,
+}
+
+impl DepthPolygon {
+    //None for polygons without area
+    pub fn new(points: Vec<Vector3>, color: Rgba) -> Option<DepthPolygon> {
+        //newell's method, it gives the normal of any planar polygon
+        let mut normal: Vector3 = (0., 0., 0.);
+        for i in 0..points.len() {
+            let (a, b) = (points[i], points[(i + 1) % points.len()]);
+            normal.0 += (a.1 - b.1) * (a.2 + b.2);
+            normal.1 += (a.2 - b.2) * (a.0 + b.0);
+            normal.2 += (a.0 - b.0) * (a.1 + b.1);
+        }
+        let normal_length = length(normal);
+        if points.len() < 3 || normal_length < f64::EPSILON {
+            return None;
+        }
+        let normal = scale(normal, 1. / normal_length);
+        Some(DepthPolygon {
+            offset: -dot(normal, points[0]),
+            points,
+            color,
+            normal,
+            moved: false,
+        })
+    }
+
+    pub fn extent(&self, axis: usize) -> (f64, f64) {
+        let value = |point: &Vector3| match axis {
+            0 => point.0,
+            1 => point.1,
+            _ => point.2,
+        };
+        self.points
+            .iter()
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), point| {
+                (min.min(value(point)), max.max(value(point)))
+            })
+    }
+
+    //signed distance of a point to the plane of the polygon
+    pub fn distance(&self, point: Vector3) -> f64 {
+        dot(self.normal, point) + self.offset
+    }
+
+    //depth of the plane of the polygon at a pixel, None when the plane is seen edge-on
+    pub fn depth_at(&self, x: f64, y: f64) -> Option<f64> {
+        match self.normal.2.abs() < PLANE_EPSILON {
+            true => None,
+            false => Some(-(self.normal.0 * x + self.normal.1 * y + self.offset) / self.normal.2),
+        }
+    }
+
+    //signed distance of a homogeneous point to the plane of the polygon, scaled by its w.
+    //for a direction, with w = 0, it tells the side of the plane the direction points to
+    pub fn side_of(&self, point: HomogeneousPoint) -> f64 {
+        dot(self.normal, (point.0, point.1, point.2)) + self.offset * point.3
+    }
+
+    //whether all the points are on the side of the plane of the polygon the viewer is, or on the other one.
+    //the viewer looks along +z, so a plane seen edge-on has no side facing it
+    fn has_on_side(&self, points: &[Vector3], viewer_side: bool) -> bool {
+        if self.normal.2.abs() < PLANE_EPSILON {
+            return false;
+        }
+        let towards_viewer = match self.normal.2 < 0. {
+            true => 1.,
+            false => -1.,
+        };
+        let wanted = match viewer_side {
+            true => towards_viewer,
+            false => -towards_viewer,
+        };
+        points
+            .iter()
+            .all(|point| self.distance(*point) * wanted >= -PLANE_EPSILON)
+    }
+
+    //split the polygon by the plane of another one, None when it lies on one side of it
+    pub fn split(&self, plane: &DepthPolygon) -> Option<(DepthPolygon, DepthPolygon)> {
+        let distances: Vec<f64> = self.points.iter().map(|p| plane.distance(*p)).collect();
+        if distances.iter().all(|d| *d >= -PLANE_EPSILON)
+            || distances.iter().all(|d| *d <= PLANE_EPSILON)
+        {
+            return None;
+        }
+        let (mut front, mut back): (Vec<Vector3>, Vec<Vector3>) = (vec![], vec![]);
+        for i in 0..self.points.len() {
+            let j = (i + 1) % self.points.len();
+            let (point, d_point, d_next) = (self.points[i], distances[i], distances[j]);
+            if d_point >= -PLANE_EPSILON {
+                front.push(point);
+            }
+            if d_point <= PLANE_EPSILON {
+                back.push(point);
+            }
+            if (d_point > PLANE_EPSILON && d_next < -PLANE_EPSILON)
+                || (d_point < -PLANE_EPSILON && d_next > PLANE_EPSILON)
+            {
+                let crossing = lerp(point, self.points[j], d_point / (d_point - d_next));
+                front.push(crossing);
+                back.push(crossing);
+            }
+        }
+        match (
+            DepthPolygon::new(front, self.color),
+            DepthPolygon::new(back, self.color),
+        ) {
+            (Some(front), Some(back)) => Some((front, back)),
+            _ => None,
+        }
+    }
+
+    pub fn outline(&self) -> Outline {
+        self.points.iter().map(|point| (point.0, point.1)).collect()
+    }
+}
+
+fn ranges_overlap(a: (f64, f64), b: (f64, f64)) -> bool {
+    a.0 < b.1 - PLANE_EPSILON && b.0 < a.1 - PLANE_EPSILON
+}
+
+//whether the projections of two convex polygons overlap, looking for an edge of either one that separates them
+fn projections_overlap(a: &DepthPolygon, b: &DepthPolygon) -> bool {
+    for polygon in [a, b] {
+        for i in 0..polygon.points.len() {
+            let (p0, p1) = (
+                polygon.points[i],
+                polygon.points[(i + 1) % polygon.points.len()],
+            );
+            let axis = (p0.1 - p1.1, p1.0 - p0.0);
+            let project = |other: &DepthPolygon| {
+                other
+                    .points
+                    .iter()
+                    .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), point| {
+                        let value = axis.0 * point.0 + axis.1 * point.1;
+                        (min.min(value), max.max(value))
+                    })
+            };
+            if !ranges_overlap(project(a), project(b)) {
+                return false;
+            }
+        }
+    }
+    true
+}
+
+//the tests of newell, newell and sancha: whether back can be drawn before front without hiding any part of it
+fn can_draw_before(back: &DepthPolygon, front: &DepthPolygon) -> bool {
+    !ranges_overlap(back.extent(0), front.extent(0))
+        || !ranges_overlap(back.extent(1), front.extent(1))
+        || front.has_on_side(&back.points, false)
+        || back.has_on_side(&front.points, true)
+        || !projections_overlap(back, front)
+}
+
+fn sort_back_to_front(polygons: &mut [DepthPolygon]) {
+    polygons.sort_by(|a, b| {
+        b.extent(2)
+            .1
+            .partial_cmp(&a.extent(2).1)
+            .unwrap_or(Ordering::Equal)
+    });
+}
+
+//order the polygons from back to front, so drawing them in order leaves the closest ones on top.
+//polygons that cannot be ordered, because they overlap cyclically or go through each other, are split
+pub fn painter_order(mut polygons: Vec<DepthPolygon>) -> Vec<DepthPolygon> {
+    sort_back_to_front(&mut polygons);
+    let mut ordered: Vec<DepthPolygon> = vec![];
+    //every split adds a polygon, so the splits are limited to keep pathological scenes from growing forever
+    let mut splits_left = 16 * polygons.len() + 64;
+
+    while !polygons.is_empty() {
+        let back = &polygons[0];
+        let back_near = back.extent(2).0;
+        let mut blocking: Option<usize> = None;
+        for (i, front) in polygons.iter().enumerate().skip(1) {
+            //the rest of the list is entirely in front of back
+            if front.extent(2).1 <= back_near {
+                break;
+            }
+            if !can_draw_before(back, front) {
+                blocking = Some(i);
+                break;
+            }
+        }
+
+        let i = match blocking {
+            Some(i) => i,
+            None => {
+                ordered.push(polygons.remove(0));
+                continue;
+            }
+        };
+        let front = &polygons[i];
+        if can_draw_before(front, back) && !front.moved {
+            let mut front = polygons.remove(i);
+            front.moved = true;
+            polygons.insert(0, front);
+            continue;
+        }
+
+        //split one of them by the plane of the other one
+        let pieces = match splits_left > 0 {
+            true => back
+                .split(front)
+                .map(|pieces| (0, pieces))
+                .or_else(|| front.split(back).map(|pieces| (i, pieces))),
+            false => None,
+        };
+        match pieces {
+            Some((index, (piece_a, piece_b))) => {
+                splits_left -= 1;
+                polygons.remove(index);
+                polygons.push(piece_a);
+                polygons.push(piece_b);
+                for polygon in polygons.iter_mut() {
+                    polygon.moved = false;
+                }
+                sort_back_to_front(&mut polygons);
+            }
+            //nothing else can be done, keep the depth order
+            None => ordered.push(polygons.remove(0)),
+        }
+    }
+    ordered
+}
+
+//a polygon clipped to the view volume of a clip space matrix and projected to the pixels of an area,
+//None when nothing of it is left
+pub fn project_polygon(
+    points: &[HomogeneousPoint],
+    color: Rgba,
+    matrix: &ArrayBase<OwnedRepr<f64>, Dim<[usize; 2]>>,
+    area: &Edge,
+) -> Option<DepthPolygon> {
+    let clip_points: Vec<HomogeneousPoint> = points
+        .iter()
+        .map(|point| transform_point_homogeneous(*point, matrix))
+        .collect();
+    let projected: Vec<Vector3> = clip_polygon_homogeneous(&clip_points)
+        .iter()
+        .map(|(x, y, z, w)| {
+            let (device_x, device_y) = ndc_to_device_exact(x / w, y / w, area);
+            (device_x, device_y, z / w)
+        })
+        .collect();
+    DepthPolygon::new(projected, color)
+}
+
+//the faces of a mesh clipped to the view volume and projected to the pixels of an image of the given size,
+//each one with the average color of its vertices. back faces are left out when the mesh culls them
+pub fn project_faces(
+    mesh: &Mesh,
+    width: usize,
+    height: usize,
+    camera: Option<&Camera>,
+    viewport: Option<&WindowViewport>,
+) -> Vec<DepthPolygon> {
+    let (matrix, area) = view_setup(width, height, camera, viewport);
+    let front_faces = match mesh.cull_back_faces {
+        true => mesh.front_faces(&matrix),
+        false => vec![true; mesh.faces.len()],
+    };
+    let mut polygons = vec![];
+    for ((a, b, c), front) in mesh.faces.iter().zip(front_faces) {
+        if !front {
+            continue;
+        }
+        let corners = [*a, *b, *c].map(|index| mesh.vertices[index]);
+        if let Some(polygon) =
+            project_polygon(&corners, mesh.face_color(&(*a, *b, *c)), &matrix, &area)
+        {
+            polygons.push(polygon);
+        }
+    }
+    polygons
+}
+
+//fill a convex polygon, the pixels whose centers are inside it
+pub fn fill_convex_polygon(image: &mut Image, outline: &[(f64, f64)], color: &Rgba) {
+    if outline.len() < 3 || image.is_empty() {
+        return;
+    }
+    let (width, height) = (image[0].len() as i32, image.len() as i32);
+    let y_min = outline.iter().map(|p| p.1).fold(f64::INFINITY, f64::min);
+    let y_max = outline
+        .iter()
+        .map(|p| p.1)
+        .fold(f64::NEG_INFINITY, f64::max);
+
+    for y in (y_min.ceil() as i32).max(0)..=(y_max.floor() as i32).min(height - 1) {
+        if let Some((x_start, x_end)) = convex_span(outline, y as f64) {
+            for x in (x_start.ceil() as i32).max(0)..=(x_end.floor() as i32).min(width - 1) {
+                image[y as usize][x as usize] = *color;
+            }
+        }
+    }
+}
+
+//where a row crosses a convex polygon, None when it misses it
+pub fn convex_span(outline: &[(f64, f64)], row: f64) -> Option<(f64, f64)> {
+    let (mut x_start, mut x_end) = (f64::INFINITY, f64::NEG_INFINITY);
+    for i in 0..outline.len() {
+        let (a, b) = (outline[i], outline[(i + 1) % outline.len()]);
+        if row < a.1.min(b.1) || row > a.1.max(b.1) {
+            continue;
+        }
+        //a horizontal edge on the row covers it from one end to the other
+        let (x0, x1) = match (b.1 - a.1).abs() < f64::EPSILON {
+            true => (a.0.min(b.0), a.0.max(b.0)),
+            false => {
+                let x = a.0 + (row - a.1) * (b.0 - a.0) / (b.1 - a.1);
+                (x, x)
+            }
+        };
+        x_start = x_start.min(x0);
+        x_end = x_end.max(x1);
+    }
+    match x_start <= x_end {
+        true => Some((x_start, x_end)),
+        false => None,
+    }
+}
+
+//draw the faces of a mesh with the painter's algorithm, each one with a flat color
+pub fn draw_mesh_painter(
+    image: Image,
+    mesh: &Mesh,
+    camera: Option<&Camera>,
+    viewport: Option<&WindowViewport>,
+) -> Image {
+    let mut new_image: Image = image.clone();
+    let polygons = project_faces(mesh, image[0].len(), image.len(), camera, viewport);
+    for polygon in painter_order(polygons) {
+        fill_convex_polygon(&mut new_image, &polygon.outline(), &polygon.color);
+    }
+    new_image
+}
+
+//the ordered polygons as an svg document, drawn in order so the closest ones end on top
+pub fn polygons_to_svg(polygons: &[DepthPolygon], width: usize, height: usize) -> String {
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{0}\" height=\"{1}\" viewBox=\"0 0 {0} {1}\">\n",
+        width, height
+    );
+    for polygon in polygons.iter() {
+        let points: Vec<String> = polygon
+            .outline()
+            .iter()
+            .map(|(x, y)| format!("{:.3},{:.3}", x, y))
+            .collect();
+        let [r, g, b, a] = polygon.color;
+        svg.push_str(&format!(
+            "  <polygon points=\"{}\" fill=\"rgb({},{},{})\" fill-opacity=\"{:.3}\"/>\n",
+            points.join(" "),
+            r,
+            g,
+            b,
+            a as f64 / 255.
+        ));
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::raster::fixtures::compare_with_depth_buffer;
+
+    fn square(z: f64, color: Rgba) -> DepthPolygon {
+        DepthPolygon::new(
+            vec![(0., 0., z), (10., 0., z), (10., 10., z), (0., 10., z)],
+            color,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn farther_polygons_are_drawn_first() {
+        let order = painter_order(vec![
+            square(1., [255, 0, 0, 255]),
+            square(5., [0, 0, 255, 255]),
+        ]);
+        let colors: Vec<Rgba> = order.iter().map(|polygon| polygon.color).collect();
+        assert_eq!(colors, vec![[0, 0, 255, 255], [255, 0, 0, 255]]);
+    }
+
+    #[test]
+    fn polygons_going_through_each_other_are_split() {
+        let tilted = DepthPolygon::new(
+            vec![(0., 0., -5.), (10., 0., 5.), (10., 10., 5.), (0., 10., -5.)],
+            [0; 4],
+        )
+        .unwrap();
+        let order = painter_order(vec![square(0., [255; 4]), tilted]);
+        assert_eq!(order.len(), 3);
+        //the half of the tilted square behind the flat one comes before it, and the other half after it
+        assert!(order[0].extent(2).0 >= -PLANE_EPSILON && order[2].extent(2).1 <= PLANE_EPSILON);
+    }
+
+    #[test]
+    fn painting_matches_the_depth_buffer() {
+        let (_, different) =
+            compare_with_depth_buffer(|image, mesh| draw_mesh_painter(image, mesh, None, None));
+        assert!(different < 10, "{} pixels are different", different);
+    }
+}
@@ -0,0 +1,376 @@
+use crate::camera::Camera;
+use crate::common::{Image, Vector3};
+use crate::raytrace::{
+    reflect, refract, refraction_ratio, to_color, to_rgba, Color, Hit, Material, Ray, Scene,
+    ViewRays,
+};
+use crate::vector::{add, cross, dot, length, normalize, scale, subtract};
+use crate::viewport::WindowViewport;
+
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::f64::consts::PI;
+
+//bounces a path makes before russian roulette can end it
+const ROULETTE_BOUNCES: usize = 3;
+//the most and the least a path is kept by russian roulette, so bright paths still end and dim ones go on a bit
+const SURVIVAL_MAX: f64 = 0.95;
+const SURVIVAL_MIN: f64 = 0.05;
+
+//two directions perpendicular to a unit vector and to each other
+fn orthonormal_basis(axis: Vector3) -> (Vector3, Vector3) {
+    let helper = match axis.0.abs() > 0.9 {
+        true => (0., 1., 0.),
+        false => (1., 0., 0.),
+    };
+    let tangent = normalize(cross(helper, axis));
+    (tangent, cross(axis, tangent))
+}
+
+//a direction around an axis with a probability proportional to cos^exponent of the angle to it.
+//an exponent of 1 gives the cosine weighted hemisphere of a diffuse surface
+fn sample_lobe(axis: Vector3, exponent: f64, rng: &mut StdRng) -> Vector3 {
+    let cosine = rng.gen::<f64>().powf(1. / (exponent + 1.));
+    let sine = (1. - cosine * cosine).max(0.).sqrt();
+    let angle = 2. * PI * rng.gen::<f64>();
+    let (tangent, bitangent) = orthonormal_basis(axis);
+    normalize(add(
+        scale(axis, cosine),
+        add(
+            scale(tangent, sine * angle.cos()),
+            scale(bitangent, sine * angle.sin()),
+        ),
+    ))
+}
+
+//how much light coming from to_light leaves towards to_viewer: a lambertian diffuse lobe and a normalized
+//phong glossy lobe around the mirror direction, weighted by the part of the light the surface takes
+fn surface_brdf(hit: &Hit, normal: Vector3, to_viewer: Vector3, to_light: Vector3) -> Color {
+    let material = hit.material;
+    let surface = 1. - material.reflectivity - material.transparency;
+    let mirror = reflect(scale(to_viewer, -1.), normal);
+    let glossy = material.specular * (material.shininess + 2.) / (2. * PI)
+        * dot(mirror, to_light).max(0.).powf(material.shininess);
+    hit.color
+        .map(|channel| surface * (channel * material.diffuse / PI + glossy))
+}
+
+//the light reaching a point of a surface straight from the lights, sampling one point of each area light.
+//a light lights a diffuse surface as much as it does in the whitted ray tracer
+fn direct_light(
+    scene: &Scene,
+    hit: &Hit,
+    normal: Vector3,
+    to_viewer: Vector3,
+    rng: &mut StdRng,
+) -> Color {
+    let mut light: Color = [0.; 3];
+    for source in scene.lights.iter() {
+        let (to_light, intensity) = source.incoming(hit.point);
+        let cosine = dot(normal, to_light);
+        if cosine <= 0. || scene.in_shadow(hit.point, to_light, source.distance(hit.point)) {
+            continue;
+        }
+        let brdf = surface_brdf(hit, normal, to_viewer, to_light);
+        for (channel, value) in light.iter_mut().enumerate() {
+            *value += intensity[channel] * PI * brdf[channel] * cosine;
+        }
+    }
+    for area_light in scene.area_lights.iter() {
+        let sample = area_light.point_at(rng.gen(), rng.gen());
+        let offset = subtract(sample, hit.point);
+        let distance = length(offset);
+        let to_light = scale(offset, 1. / distance);
+        let cosine = dot(normal, to_light);
+        let light_cosine = -dot(area_light.normal, to_light);
+        if cosine <= 0. || light_cosine <= 0. || scene.in_shadow(hit.point, to_light, distance) {
+            continue;
+        }
+        //the probability of the sample is 1 / area, turned into a solid angle around the point
+        let geometry = cosine * light_cosine * area_light.area / (distance * distance);
+        let brdf = surface_brdf(hit, normal, to_viewer, to_light);
+        for (channel, value) in light.iter_mut().enumerate() {
+            *value += area_light.emission[channel] * brdf[channel] * geometry;
+        }
+    }
+    light
+}
+
+//the light arriving along a ray, following one random path through the scene. the light straight from the
+//lights is added at each diffuse or glossy bounce, so the lights only count when a ray hits them after
+//the camera, a mirror or a refraction
+pub fn trace_path(scene: &Scene, ray: &Ray, max_bounces: usize, rng: &mut StdRng) -> Color {
+    let mut radiance: Color = [0.; 3];
+    let mut throughput: Color = [1.; 3];
+    let mut ray = *ray;
+    let mut count_emission = true;
+
+    for bounce in 0..max_bounces {
+        let hit = scene.intersect(&ray, f64::INFINITY);
+        let limit = hit.as_ref().map_or(f64::INFINITY, |hit| hit.distance);
+        //lights absorb what reaches them, and give off light from their front side only
+        if let Some((_, area_light)) = scene.intersect_area_light(&ray, limit) {
+            if count_emission && dot(area_light.normal, ray.direction) < 0. {
+                for (channel, value) in radiance.iter_mut().enumerate() {
+                    *value += throughput[channel] * area_light.emission[channel];
+                }
+            }
+            break;
+        }
+        let hit = match hit {
+            Some(hit) => hit,
+            None => {
+                let background = to_color(&scene.background);
+                for (channel, value) in radiance.iter_mut().enumerate() {
+                    *value += throughput[channel] * background[channel];
+                }
+                break;
+            }
+        };
+
+        let material: &Material = hit.material;
+        let outside = dot(ray.direction, hit.normal) < 0.;
+        let normal = match outside {
+            true => hit.normal,
+            false => scale(hit.normal, -1.),
+        };
+        let to_viewer = scale(ray.direction, -1.);
+        let direct = direct_light(scene, &hit, normal, to_viewer, rng);
+        for (channel, value) in radiance.iter_mut().enumerate() {
+            *value += throughput[channel] * direct[channel];
+        }
+
+        //one of the ways the surface scatters light, picked with the weight it has, so the weight cancels out
+        let choice = rng.gen::<f64>();
+        let direction = if choice < material.reflectivity {
+            count_emission = true;
+            reflect(ray.direction, normal)
+        } else if choice < material.reflectivity + material.transparency {
+            count_emission = true;
+            refract(ray.direction, normal, refraction_ratio(material, outside))
+                .unwrap_or_else(|| reflect(ray.direction, normal))
+        } else {
+            let lobes = material.diffuse + material.specular;
+            if lobes <= 0. {
+                break;
+            }
+            count_emission = false;
+            let diffuse_probability = material.diffuse / lobes;
+            if rng.gen::<f64>() < diffuse_probability {
+                //the cosine and the 1 / pi of the lobe cancel out with its probability
+                for (channel, value) in throughput.iter_mut().enumerate() {
+                    *value *= hit.color[channel] * material.diffuse / diffuse_probability;
+                }
+                sample_lobe(normal, 1., rng)
+            } else {
+                let mirror = reflect(ray.direction, normal);
+                let direction = sample_lobe(mirror, material.shininess, rng);
+                let cosine = dot(direction, normal);
+                if cosine <= 0. {
+                    break;
+                }
+                let weight = material.specular * (material.shininess + 2.)
+                    / (material.shininess + 1.)
+                    * cosine
+                    / (1. - diffuse_probability);
+                throughput = throughput.map(|channel| channel * weight);
+                direction
+            }
+        };
+
+        if bounce + 1 >= ROULETTE_BOUNCES {
+            let survival = throughput
+                .iter()
+                .cloned()
+                .fold(0., f64::max)
+                .clamp(SURVIVAL_MIN, SURVIVAL_MAX);
+            if rng.gen::<f64>() >= survival {
+                break;
+            }
+            throughput = throughput.map(|channel| channel / survival);
+        }
+        ray = Ray {
+            origin: hit.point,
+            direction,
+        };
+    }
+    radiance
+}
+
+//a path traced view of a scene that gets better as samples are added. each call to render adds samples
+//to the ones taken before, and the image shows their average. a run always gives the same image for a seed
+#[pyclass]
+pub struct PathTracer {
+    scene: Scene,
+    view: ViewRays,
+    //the sum of the samples of each pixel of the view, in the order of its pixels
+    accumulated: Vec<Color>,
+    #[pyo3(get)]
+    pub samples: usize,
+    #[pyo3(get, set)]
+    pub max_bounces: usize,
+    #[pyo3(get)]
+    pub seed: u64,
+    rng: StdRng,
+}
+
+impl PathTracer {
+    //None when the view can not be inverted
+    pub fn new(
+        scene: Scene,
+        width: usize,
+        height: usize,
+        seed: u64,
+        max_bounces: usize,
+        camera: Option<&Camera>,
+        viewport: Option<&WindowViewport>,
+    ) -> Option<PathTracer> {
+        let view = ViewRays::new(width, height, camera, viewport)?;
+        Some(PathTracer {
+            accumulated: vec![[0.; 3]; view.pixels().len()],
+            scene,
+            view,
+            samples: 0,
+            max_bounces,
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+        })
+    }
+
+    //trace one more path through each pixel, through a random point of it so the edges are smoothed
+    pub fn add_samples(&mut self, samples: usize) {
+        let pixels = self.view.pixels();
+        for _ in 0..samples {
+            for ((x, y), sum) in pixels.iter().zip(self.accumulated.iter_mut()) {
+                let ray = self.view.through(
+                    *x as f64 + self.rng.gen::<f64>() - 0.5,
+                    *y as f64 + self.rng.gen::<f64>() - 0.5,
+                );
+                let color = trace_path(&self.scene, &ray, self.max_bounces, &mut self.rng);
+                for channel in 0..3 {
+                    sum[channel] += color[channel];
+                }
+            }
+            self.samples += 1;
+        }
+    }
+
+    //the average of the samples taken, drawn over the pixels of the view
+    pub fn draw(&self, image: Image) -> Image {
+        let mut new_image: Image = image.clone();
+        if self.samples == 0 {
+            return new_image;
+        }
+        for ((x, y), sum) in self.view.pixels().iter().zip(self.accumulated.iter()) {
+            new_image[*y][*x] = to_rgba(&sum.map(|channel| channel / self.samples as f64));
+        }
+        new_image
+    }
+
+    //forget the samples and start the random numbers over, so the next renders repeat the run
+    pub fn reset(&mut self) {
+        self.accumulated = vec![[0.; 3]; self.accumulated.len()];
+        self.samples = 0;
+        self.rng = StdRng::seed_from_u64(self.seed);
+    }
+}
+
+#[pymethods]
+impl PathTracer {
+    #[new]
+    #[args(seed = "0", max_bounces = "16", camera = "None", viewport = "None")]
+    fn py_new(
+        scene: Scene,
+        width: usize,
+        height: usize,
+        seed: u64,
+        max_bounces: usize,
+        camera: Option<Camera>,
+        viewport: Option<WindowViewport>,
+    ) -> PyResult<Self> {
+        if width == 0 || height == 0 {
+            return Err(PyValueError::new_err("the image can not be empty"));
+        }
+        PathTracer::new(
+            scene,
+            width,
+            height,
+            seed,
+            max_bounces,
+            camera.as_ref(),
+            viewport.as_ref(),
+        )
+        .ok_or_else(|| PyValueError::new_err("the view can not be inverted"))
+    }
+
+    //add samples to each pixel and draw the image with all the samples taken so far
+    #[args(samples = "1")]
+    fn render(&mut self, image: Image, samples: usize) -> PyResult<Image> {
+        if image.len() != self.view.height || image.first().map_or(0, Vec::len) != self.view.width {
+            return Err(PyValueError::new_err(
+                "the image must have the size the path tracer was made for",
+            ));
+        }
+        self.add_samples(samples);
+        Ok(self.draw(image))
+    }
+
+    #[pyo3(name = "reset")]
+    fn py_reset(&mut self) {
+        self.reset();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::light::Light;
+    use crate::mesh::Mesh;
+
+    fn scene() -> Scene {
+        let mut scene = Scene::new(0.1, [0, 0, 0, 255]);
+        let triangle = Mesh::from_triangles(
+            &[[(0., 0., 0., 1.), (20., 0., 0., 1.), (0., 20., 0., 1.)]],
+            [200; 4],
+        );
+        scene.push_mesh(&triangle, None);
+        scene
+            .lights
+            .push(Light::point((5., 5., -50.), [1.; 3], (1., 0., 0.)));
+        scene
+    }
+
+    #[test]
+    fn diffuse_samples_follow_the_cosine() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let axis = normalize((1., 2., 3.));
+        let count = 20000;
+        let mut total = 0.;
+        for _ in 0..count {
+            let cosine = dot(sample_lobe(axis, 1., &mut rng), axis);
+            assert!(cosine >= 0.);
+            total += cosine;
+        }
+        //the mean of the cosine over a cosine weighted hemisphere is 2 / 3
+        assert!((total / count as f64 - 2. / 3.).abs() < 0.01);
+    }
+
+    #[test]
+    fn runs_with_the_same_seed_give_the_same_image() {
+        let image: Image = vec![vec![[0, 0, 0, 255]; 20]; 20];
+        let mut tracer = PathTracer::new(scene(), 20, 20, 3, 4, None, None).unwrap();
+        tracer.add_samples(2);
+        let first = tracer.draw(image.clone());
+        tracer.reset();
+        tracer.add_samples(2);
+        assert_eq!(tracer.draw(image.clone()), first);
+        let mut other = PathTracer::new(scene(), 20, 20, 3, 4, None, None).unwrap();
+        other.add_samples(2);
+        assert_eq!(other.draw(image.clone()), first);
+        //the triangle is lit, the background stays black
+        assert!(first[15][3][0] > 0);
+        assert_eq!(first[2][17], [0, 0, 0, 255]);
+    }
+}
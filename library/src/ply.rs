@@ -0,0 +1,401 @@
+use crate::common::Rgba;
+use crate::mesh::{point_to_vector, Mesh, WIREFRAME_COLOR};
+
+use std::fs;
+use std::io::{Error, ErrorKind, Result};
+use std::str::SplitWhitespace;
+
+fn invalid_data(message: String) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
+
+#[derive(Clone, Copy)]
+enum ScalarType {
+    Int8,
+    Uint8,
+    Int16,
+    Uint16,
+    Int32,
+    Uint32,
+    Float32,
+    Float64,
+}
+
+impl ScalarType {
+    fn parse(name: &str) -> Result<ScalarType> {
+        match name {
+            "char" | "int8" => Ok(ScalarType::Int8),
+            "uchar" | "uint8" => Ok(ScalarType::Uint8),
+            "short" | "int16" => Ok(ScalarType::Int16),
+            "ushort" | "uint16" => Ok(ScalarType::Uint16),
+            "int" | "int32" => Ok(ScalarType::Int32),
+            "uint" | "uint32" => Ok(ScalarType::Uint32),
+            "float" | "float32" => Ok(ScalarType::Float32),
+            "double" | "float64" => Ok(ScalarType::Float64),
+            _ => Err(invalid_data(format!("unknown property type '{}'", name))),
+        }
+    }
+
+    fn size(&self) -> usize {
+        match self {
+            ScalarType::Int8 | ScalarType::Uint8 => 1,
+            ScalarType::Int16 | ScalarType::Uint16 => 2,
+            ScalarType::Int32 | ScalarType::Uint32 | ScalarType::Float32 => 4,
+            ScalarType::Float64 => 8,
+        }
+    }
+
+    //colors stored as integers go from 0 to 255, and as floats from 0 to 1
+    fn is_float(&self) -> bool {
+        matches!(self, ScalarType::Float32 | ScalarType::Float64)
+    }
+}
+
+struct Property {
+    name: String,
+    kind: ScalarType,
+    //type of the item count, for list properties
+    count_kind: Option<ScalarType>,
+}
+
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+//the values after the header, either whitespace separated text or little endian bytes
+enum Body<'a> {
+    Ascii(SplitWhitespace<'a>),
+    Binary(&'a [u8], usize),
+}
+
+impl<'a> Body<'a> {
+    fn read(&mut self, kind: ScalarType) -> Result<f64> {
+        match self {
+            Body::Ascii(tokens) => {
+                let token = tokens
+                    .next()
+                    .ok_or_else(|| invalid_data("the file ends too early".to_string()))?;
+                token
+                    .parse::<f64>()
+                    .map_err(|_| invalid_data(format!("invalid number '{}'", token)))
+            }
+            Body::Binary(bytes, offset) => {
+                let size = kind.size();
+                if *offset + size > bytes.len() {
+                    return Err(invalid_data("the file ends too early".to_string()));
+                }
+                let value = &bytes[*offset..*offset + size];
+                *offset += size;
+                Ok(match kind {
+                    ScalarType::Int8 => value[0] as i8 as f64,
+                    ScalarType::Uint8 => value[0] as f64,
+                    ScalarType::Int16 => i16::from_le_bytes([value[0], value[1]]) as f64,
+                    ScalarType::Uint16 => u16::from_le_bytes([value[0], value[1]]) as f64,
+                    ScalarType::Int32 => i32::from_le_bytes(value.try_into().unwrap()) as f64,
+                    ScalarType::Uint32 => u32::from_le_bytes(value.try_into().unwrap()) as f64,
+                    ScalarType::Float32 => f32::from_le_bytes(value.try_into().unwrap()) as f64,
+                    ScalarType::Float64 => f64::from_le_bytes(value.try_into().unwrap()),
+                })
+            }
+        }
+    }
+}
+
+//a count or an index read as a number, which has to be a whole number that is not negative
+fn to_index(value: f64, what: &str) -> Result<usize> {
+    if value < 0. || value.fract() != 0. || value > u32::MAX as f64 {
+        return Err(invalid_data(format!("invalid {} '{}'", what, value)));
+    }
+    Ok(value as usize)
+}
+
+//split the file in its elements and the position where their values start
+fn parse_header(bytes: &[u8]) -> Result<(bool, Vec<Element>, usize)> {
+    let end = b"end_header";
+    let header_end = bytes
+        .windows(end.len())
+        .position(|window| window == end)
+        .ok_or_else(|| invalid_data("the header has no end_header".to_string()))?;
+    //the values start after the line break that follows end_header
+    let mut body_start = header_end + end.len();
+    while body_start < bytes.len() && bytes[body_start] != b'\n' {
+        body_start += 1;
+    }
+    body_start += 1;
+
+    let header = String::from_utf8_lossy(&bytes[..header_end]);
+    let mut lines = header.lines();
+    if lines.next().map(str::trim) != Some("ply") {
+        return Err(invalid_data("not a ply file".to_string()));
+    }
+
+    let mut binary = false;
+    let mut elements: Vec<Element> = vec![];
+    for line in lines {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        match tokens.as_slice() {
+            ["format", "ascii", ..] => binary = false,
+            ["format", "binary_little_endian", ..] => binary = true,
+            ["format", format, ..] => {
+                return Err(invalid_data(format!("unsupported format '{}'", format)))
+            }
+            ["element", name, count] => elements.push(Element {
+                name: name.to_string(),
+                count: count
+                    .parse()
+                    .map_err(|_| invalid_data(format!("invalid element count '{}'", count)))?,
+                properties: vec![],
+            }),
+            ["property", "list", count_kind, kind, name] => {
+                let element = elements
+                    .last_mut()
+                    .ok_or_else(|| invalid_data("property before element".to_string()))?;
+                element.properties.push(Property {
+                    name: name.to_string(),
+                    kind: ScalarType::parse(kind)?,
+                    count_kind: Some(ScalarType::parse(count_kind)?),
+                });
+            }
+            ["property", kind, name] => {
+                let element = elements
+                    .last_mut()
+                    .ok_or_else(|| invalid_data("property before element".to_string()))?;
+                element.properties.push(Property {
+                    name: name.to_string(),
+                    kind: ScalarType::parse(kind)?,
+                    count_kind: None,
+                });
+            }
+            _ => {}
+        }
+    }
+    Ok((binary, elements, body_start.min(bytes.len())))
+}
+
+//read an ascii or binary little endian ply. the vertex element gives the positions, with optional normals and
+//red, green, blue and alpha colors, and the face element gives polygons, split into triangle fans
+pub fn parse_ply(bytes: &[u8]) -> Result<Mesh> {
+    let (binary, elements, body_start) = parse_header(bytes)?;
+    let mut body = match binary {
+        true => Body::Binary(bytes, body_start),
+        false => Body::Ascii(
+            std::str::from_utf8(&bytes[body_start..])
+                .map_err(|_| invalid_data("the ascii values are not text".to_string()))?
+                .split_whitespace(),
+        ),
+    };
+    let mut mesh = Mesh::default();
+
+    for element in elements.iter() {
+        let index_of = |name: &str| element.properties.iter().position(|p| p.name == name);
+        let position = [index_of("x"), index_of("y"), index_of("z")];
+        let normal = [index_of("nx"), index_of("ny"), index_of("nz")];
+        let color = [
+            index_of("red").or_else(|| index_of("diffuse_red")),
+            index_of("green").or_else(|| index_of("diffuse_green")),
+            index_of("blue").or_else(|| index_of("diffuse_blue")),
+            index_of("alpha"),
+        ];
+        let face = index_of("vertex_indices").or_else(|| index_of("vertex_index"));
+
+        for _ in 0..element.count {
+            let mut values: Vec<Vec<f64>> = vec![];
+            for property in element.properties.iter() {
+                let count = match property.count_kind {
+                    Some(count_kind) => to_index(body.read(count_kind)?, "list count")?,
+                    None => 1,
+                };
+                //the count comes from the file, so nothing is reserved for it up front
+                let mut list = vec![];
+                for _ in 0..count {
+                    list.push(body.read(property.kind)?);
+                }
+                values.push(list);
+            }
+            let scalar = |index: Option<usize>| index.map(|index| values[index][0]);
+
+            if element.name == "vertex" {
+                let [x, y, z] = position.map(scalar);
+                match (x, y, z) {
+                    (Some(x), Some(y), Some(z)) => mesh.vertices.push((x, y, z, 1.)),
+                    _ => return Err(invalid_data("a vertex needs x, y and z".to_string())),
+                }
+                if let [Some(nx), Some(ny), Some(nz)] = normal.map(scalar) {
+                    mesh.normals.push((nx, ny, nz));
+                }
+                let mut rgba: Rgba = WIREFRAME_COLOR;
+                for (channel, index) in color.iter().enumerate() {
+                    if let Some(value) = scalar(*index) {
+                        let value = match element.properties[index.unwrap()].kind.is_float() {
+                            true => value * 255.,
+                            false => value,
+                        };
+                        rgba[channel] = value.clamp(0., 255.).round() as u8;
+                    }
+                }
+                mesh.colors.push(rgba);
+            } else if element.name == "face" {
+                if let Some(face) = face {
+                    let indexes: Vec<usize> = values[face]
+                        .iter()
+                        .map(|index| to_index(*index, "vertex index"))
+                        .collect::<Result<_>>()?;
+                    for i in 1..indexes.len().saturating_sub(1) {
+                        mesh.faces.push((indexes[0], indexes[i], indexes[i + 1]));
+                    }
+                }
+            }
+        }
+    }
+
+    if mesh
+        .faces
+        .iter()
+        .any(|(a, b, c)| *a.max(b.max(c)) >= mesh.vertices.len())
+    {
+        return Err(invalid_data(
+            "a face uses a vertex that does not exist".to_string(),
+        ));
+    }
+    //normals are only kept when every vertex has one
+    if mesh.normals.len() != mesh.vertices.len() {
+        mesh.normals = vec![];
+        if !mesh.faces.is_empty() {
+            mesh.compute_normals();
+        }
+    }
+    Ok(mesh)
+}
+
+pub fn read_ply(path: &str) -> Result<Mesh> {
+    parse_ply(&fs::read(path)?)
+}
+
+//write the vertices of a mesh with their colors, their normals when the mesh has them, and its faces
+pub fn write_ply(mesh: &Mesh, path: &str, binary: bool) -> Result<()> {
+    let has_normals = mesh.normals.len() == mesh.vertices.len() && !mesh.normals.is_empty();
+    let mut header = format!(
+        "ply\nformat {} 1.0\ncomment written by cglib\nelement vertex {}\n",
+        match binary {
+            true => "binary_little_endian",
+            false => "ascii",
+        },
+        mesh.vertices.len()
+    );
+    header.push_str("property float x\nproperty float y\nproperty float z\n");
+    if has_normals {
+        header.push_str("property float nx\nproperty float ny\nproperty float nz\n");
+    }
+    header.push_str(
+        "property uchar red\nproperty uchar green\nproperty uchar blue\nproperty uchar alpha\n",
+    );
+    if !mesh.faces.is_empty() {
+        header.push_str(&format!(
+            "element face {}\nproperty list uchar int vertex_indices\n",
+            mesh.faces.len()
+        ));
+    }
+    header.push_str("end_header\n");
+
+    let mut bytes: Vec<u8> = header.into_bytes();
+    let mut text = String::new();
+    for (i, vertex) in mesh.vertices.iter().enumerate() {
+        let color = mesh.colors.get(i).unwrap_or(&WIREFRAME_COLOR);
+        let (x, y, z) = point_to_vector(*vertex);
+        let mut floats = vec![x, y, z];
+        if has_normals {
+            floats.extend([mesh.normals[i].0, mesh.normals[i].1, mesh.normals[i].2]);
+        }
+        match binary {
+            true => {
+                for value in floats {
+                    bytes.extend_from_slice(&(value as f32).to_le_bytes());
+                }
+                bytes.extend_from_slice(color);
+            }
+            false => {
+                for value in floats {
+                    text.push_str(&format!("{} ", value as f32));
+                }
+                text.push_str(&format!(
+                    "{} {} {} {}\n",
+                    color[0], color[1], color[2], color[3]
+                ));
+            }
+        }
+    }
+    for (a, b, c) in mesh.faces.iter() {
+        match binary {
+            true => {
+                bytes.push(3);
+                for index in [a, b, c] {
+                    bytes.extend_from_slice(&(*index as i32).to_le_bytes());
+                }
+            }
+            false => text.push_str(&format!("3 {} {} {}\n", a, b, c)),
+        }
+    }
+
+    bytes.extend_from_slice(text.as_bytes());
+    fs::write(path, bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TRIANGLE: &str =
+        "ply\nformat ascii 1.0\nelement vertex 3\nproperty float x\nproperty float y\n\
+        property float z\nelement face 1\nproperty list uchar int vertex_indices\nend_header\n";
+
+    #[test]
+    fn ascii_faces_are_read() {
+        let mesh =
+            parse_ply(format!("{}0 0 0\n1 0 0\n0 1 0\n3 0 1 2\n", TRIANGLE).as_bytes()).unwrap();
+        assert_eq!(mesh.vertices.len(), 3);
+        assert_eq!(mesh.faces, vec![(0, 1, 2)]);
+        assert_eq!(mesh.normals.len(), 3);
+    }
+
+    #[test]
+    fn huge_list_counts_are_an_error() {
+        for count in ["4294967295", "1e12", "-3", "nan"] {
+            let text = format!("{}0 0 0\n1 0 0\n0 1 0\n{} 0 1 2\n", TRIANGLE, count);
+            assert!(parse_ply(text.as_bytes()).is_err(), "{}", count);
+        }
+    }
+
+    #[test]
+    fn negative_and_fractional_indexes_are_an_error() {
+        for face in ["3 0 -1 2", "3 0 nan 2", "3 0 1.5 2"] {
+            let text = format!("{}0 0 0\n1 0 0\n0 1 0\n{}\n", TRIANGLE, face);
+            assert!(parse_ply(text.as_bytes()).is_err(), "{}", face);
+        }
+    }
+
+    #[test]
+    fn meshes_round_trip_in_both_formats() {
+        let mut mesh = Mesh::from_triangles(
+            &[
+                [(0., 0., 0., 1.), (1., 0., 0., 1.), (0., 1., 0., 1.)],
+                [(1., 0., 0., 1.), (1., 1., 0., 1.), (0., 1., 0., 1.)],
+            ],
+            [10, 20, 30, 255],
+        );
+        //a vertex without a color is written with the default one
+        mesh.colors.pop();
+        for binary in [false, true] {
+            let path = std::env::temp_dir().join(format!("cglib_round_trip_{}.ply", binary));
+            let path = path.to_str().unwrap();
+            write_ply(&mesh, path, binary).unwrap();
+            let read = read_ply(path).unwrap();
+            fs::remove_file(path).unwrap();
+            assert_eq!(read.vertices, mesh.vertices);
+            assert_eq!(read.faces, mesh.faces);
+            assert_eq!(read.colors[0], [10, 20, 30, 255]);
+            assert_eq!(read.colors[3], WIREFRAME_COLOR);
+        }
+    }
+}
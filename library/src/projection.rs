@@ -0,0 +1,131 @@
+use ndarray::{arr2, ArrayBase, Dim, OwnedRepr};
+
+//named parallel projections. all of them keep x and y on the screen plane and drop z,
+//so their output is still drawn by the orthographic screen projection.
+//the axonometric ones look at the object from the direction given by an azimuth (alpha) around the y axis,
+//measured from +z towards +x, and an elevation (beta) above the xz plane, both in degrees.
+//the oblique ones use alpha as the angle of the receding z axis on the screen
+
+//elevation of the isometric view, the angle whose tangent is sin(45 degrees)
+const ISOMETRIC_ELEVATION: f64 = 35.264389682754654;
+
+//orthographic view of the front (xy), top (xz) or side (zy) of the object
+fn multiview_matrix(view: &str) -> Option<ArrayBase<OwnedRepr<f64>, Dim<[usize; 2]>>> {
+    // rustfmt-ignore
+    let matrix: [[f64; 4]; 4] = match view {
+        "front" => [
+            [1., 0., 0., 0.],
+            [0., 1., 0., 0.],
+            [0., 0., 0., 0.],
+            [0., 0., 0., 1.],
+        ],
+        //seen from above, the front of the object faces the bottom of the screen
+        "top" => [
+            [1., 0., 0., 0.],
+            [0., 0., 0., 0.],
+            [0., -1., 0., 0.],
+            [0., 0., 0., 1.],
+        ],
+        //seen from the right, the front of the object faces the left of the screen
+        "side" => [
+            [0., 0., 0., 0.],
+            [0., 1., 0., 0.],
+            [-1., 0., 0., 0.],
+            [0., 0., 0., 1.],
+        ],
+        _ => return None,
+    };
+    Some(arr2(&matrix))
+}
+
+//axonometric view from the given azimuth and elevation
+fn axonometric_matrix(azimuth: f64, elevation: f64) -> ArrayBase<OwnedRepr<f64>, Dim<[usize; 2]>> {
+    let (sin_a, cos_a) = azimuth.to_radians().sin_cos();
+    let (sin_e, cos_e) = elevation.to_radians().sin_cos();
+    // rustfmt-ignore
+    let matrix: [[f64; 4]; 4] = [
+        [cos_a, -sin_e * sin_a, 0., 0.],
+        [0., cos_e, 0., 0.],
+        [-sin_a, -sin_e * cos_a, 0., 0.],
+        [0., 0., 0., 1.],
+    ];
+    arr2(&matrix)
+}
+
+//oblique view, the z axis is drawn at the given angle and scaled by the given length
+fn oblique_matrix(angle: f64, length: f64) -> ArrayBase<OwnedRepr<f64>, Dim<[usize; 2]>> {
+    let (sin, cos) = angle.to_radians().sin_cos();
+    // rustfmt-ignore
+    let matrix: [[f64; 4]; 4] = [
+        [1., 0., 0., 0.],
+        [0., 1., 0., 0.],
+        [length * cos, length * sin, 0., 0.],
+        [0., 0., 0., 1.],
+    ];
+    arr2(&matrix)
+}
+
+//get the matrix of a parallel projection by its name, returns None for unknown names.
+//  front, top, side: orthographic multi-view
+//  isometric: the three axes equally foreshortened
+//  dimetric: azimuth fixed at 45 degrees, beta is the elevation (default 20.705)
+//  trimetric: alpha is the azimuth (default 30) and beta the elevation (default 20)
+//  cavalier: receding axis at full length, alpha is its angle (default 45)
+//  cabinet: receding axis at half length, alpha is its angle (default 45)
+pub fn parallel_projection_matrix(
+    projection: &str,
+    alpha: Option<f64>,
+    beta: Option<f64>,
+) -> Option<ArrayBase<OwnedRepr<f64>, Dim<[usize; 2]>>> {
+    match projection {
+        "isometric" => Some(axonometric_matrix(45., ISOMETRIC_ELEVATION)),
+        "dimetric" => Some(axonometric_matrix(45., beta.unwrap_or(20.705))),
+        "trimetric" => Some(axonometric_matrix(
+            alpha.unwrap_or(30.),
+            beta.unwrap_or(20.),
+        )),
+        "cavalier" => Some(oblique_matrix(alpha.unwrap_or(45.), 1.)),
+        "cabinet" => Some(oblique_matrix(alpha.unwrap_or(45.), 0.5)),
+        view => multiview_matrix(view),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray::arr1;
+
+    fn project(projection: &str, point: [f64; 3]) -> (f64, f64) {
+        let matrix = parallel_projection_matrix(projection, None, None).unwrap();
+        let product = arr1(&[point[0], point[1], point[2], 1.]).dot(&matrix);
+        (product[0], product[1])
+    }
+
+    fn length(point: (f64, f64)) -> f64 {
+        point.0.hypot(point.1)
+    }
+
+    #[test]
+    fn isometric_axes_are_equally_foreshortened() {
+        let axes = [[1., 0., 0.], [0., 1., 0.], [0., 0., 1.]]
+            .map(|axis| length(project("isometric", axis)));
+        assert!((axes[0] - axes[1]).abs() < 1e-9 && (axes[1] - axes[2]).abs() < 1e-9);
+        assert!((axes[0] - (2f64 / 3.).sqrt()).abs() < 1e-9);
+    }
+
+    #[test]
+    fn oblique_projections_keep_the_front_and_shorten_the_depth() {
+        for (projection, depth) in [("cavalier", 1.), ("cabinet", 0.5)] {
+            assert_eq!(project(projection, [2., 3., 0.]), (2., 3.));
+            assert!((length(project(projection, [0., 0., 1.])) - depth).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn multiviews_drop_one_axis() {
+        assert_eq!(project("front", [1., 2., 3.]), (1., 2.));
+        assert_eq!(project("top", [1., 2., 3.]), (1., -3.));
+        assert_eq!(project("side", [1., 2., 3.]), (-3., 2.));
+        assert!(parallel_projection_matrix("sideways", None, None).is_none());
+    }
+}
@@ -0,0 +1,413 @@
+use crate::camera::Camera;
+use crate::common::{Edge, HomogeneousPoint, Image, Rgba};
+use crate::mesh::Mesh;
+use crate::operations::{transform_point_homogeneous, view_setup};
+use crate::viewport::{ndc_to_device_exact, WindowViewport};
+
+use pyo3::exceptions::PyIndexError;
+use pyo3::prelude::*;
+
+//one depth per pixel of the image, row by row, in normalized device coordinates.
+//smaller values are closer to the viewer and the pixels nothing was drawn on keep infinity
+#[pyclass]
+#[derive(Clone)]
+pub struct DepthBuffer {
+    #[pyo3(get)]
+    pub width: usize,
+    #[pyo3(get)]
+    pub height: usize,
+    #[pyo3(get)]
+    pub depth: Vec<f32>,
+}
+
+//how depth images show the depth
+pub enum DepthColormap {
+    //white for the near depth to black for the far one
+    Gray,
+    //red for the near depth through yellow, green and cyan to blue for the far one
+    Jet,
+}
+
+fn jet_color(value: f64) -> Rgba {
+    //value goes from 0 for blue to 1 for red, each channel is a trapezoid
+    let channel =
+        |center: f64| ((1.5 - (4. * value - center).abs()).clamp(0., 1.) * 255.).round() as u8;
+    [channel(3.), channel(2.), channel(1.), 255]
+}
+
+impl DepthBuffer {
+    pub fn new(width: usize, height: usize) -> DepthBuffer {
+        DepthBuffer {
+            width,
+            height,
+            depth: vec![f32::INFINITY; width * height],
+        }
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> f32 {
+        self.depth[y * self.width + x]
+    }
+
+    //the closest and the farthest depths drawn, None when nothing was drawn
+    pub fn range(&self) -> Option<(f32, f32)> {
+        self.depth
+            .iter()
+            .filter(|depth| depth.is_finite())
+            .fold(None, |range, depth| match range {
+                None => Some((*depth, *depth)),
+                Some((near, far)) => Some((near.min(*depth), far.max(*depth))),
+            })
+    }
+
+    //the depth as an image, with near and far as the ends of the colormap, by default the range drawn.
+    //depths out of the range take the color of its closest end, and the pixels nothing was drawn on are transparent
+    pub fn to_image(&self, near: Option<f64>, far: Option<f64>, colormap: &DepthColormap) -> Image {
+        let (drawn_near, drawn_far) = self.range().unwrap_or((0., 1.));
+        let near = near.unwrap_or(drawn_near as f64);
+        let far = far.unwrap_or(drawn_far as f64);
+        let mut image: Image = vec![vec![[0, 0, 0, 0]; self.width]; self.height];
+
+        for (y, row) in image.iter_mut().enumerate() {
+            for (x, pixel) in row.iter_mut().enumerate() {
+                let depth = self.get(x, y) as f64;
+                if !depth.is_finite() {
+                    continue;
+                }
+                let value = match far - near {
+                    range if range.abs() > f64::EPSILON => ((depth - near) / range).clamp(0., 1.),
+                    _ => 0.,
+                };
+                *pixel = match colormap {
+                    DepthColormap::Gray => {
+                        let gray = ((1. - value) * 255.).round() as u8;
+                        [gray, gray, gray, 255]
+                    }
+                    DepthColormap::Jet => jet_color(1. - value),
+                };
+            }
+        }
+        image
+    }
+
+    //keep the depth if it is closer than the one the pixel has, and tell whether it was kept
+    pub fn test_and_set(&mut self, x: usize, y: usize, depth: f32) -> bool {
+        let index = y * self.width + x;
+        if depth < self.depth[index] {
+            self.depth[index] = depth;
+            return true;
+        }
+        false
+    }
+}
+
+//a corner of a triangle in clip space, with its color as floats so it can be interpolated
+type ClipVertex = (HomogeneousPoint, [f64; 4]);
+
+fn interpolate_vertex(a: &ClipVertex, b: &ClipVertex, t: f64) -> ClipVertex {
+    let lerp = |x: f64, y: f64| x + (y - x) * t;
+    (
+        (
+            lerp(a.0 .0, b.0 .0),
+            lerp(a.0 .1, b.0 .1),
+            lerp(a.0 .2, b.0 .2),
+            lerp(a.0 .3, b.0 .3),
+        ),
+        [
+            lerp(a.1[0], b.1[0]),
+            lerp(a.1[1], b.1[1]),
+            lerp(a.1[2], b.1[2]),
+            lerp(a.1[3], b.1[3]),
+        ],
+    )
+}
+
+//clip a polygon against the near plane, z >= -w, so every vertex left is in front of the viewer.
+//the other planes are left to the rasterizer, which only visits the pixels of the area and skips far depths
+fn clip_near(polygon: &[ClipVertex]) -> Vec<ClipVertex> {
+    let distance = |vertex: &ClipVertex| vertex.0 .2 + vertex.0 .3;
+    let mut output: Vec<ClipVertex> = vec![];
+    for i in 0..polygon.len() {
+        let current = &polygon[i];
+        let next = &polygon[(i + 1) % polygon.len()];
+        let (d_current, d_next) = (distance(current), distance(next));
+        if d_current >= 0. {
+            output.push(*current);
+        }
+        if (d_current >= 0.) != (d_next >= 0.) {
+            output.push(interpolate_vertex(
+                current,
+                next,
+                d_current / (d_current - d_next),
+            ));
+        }
+    }
+    output
+}
+
+//how far outside of a triangle a pixel center may be and still be filled. the corners come out of the
+//projection with rounding errors, and without it the pixel centers right on an edge shared by two triangles
+//could be left out by both
+const EDGE_TOLERANCE: f64 = 1e-9;
+
+//fill a triangle, given as pixel coordinates with the depth in z and 1 / w, testing each pixel center against
+//the depth buffer. the depth is interpolated linearly in the screen and the colors with perspective correction
+fn rasterize_triangle(
+    image: &mut Image,
+    depth_buffer: &mut DepthBuffer,
+    corners: [(f64, f64, f64, f64); 3],
+    colors: [[f64; 4]; 3],
+    area: &Edge,
+) {
+    let [a, b, c] = corners;
+    let edge = |p: (f64, f64, f64, f64), q: (f64, f64, f64, f64), x: f64, y: f64| {
+        (q.0 - p.0) * (y - p.1) - (q.1 - p.1) * (x - p.0)
+    };
+    let double_area = edge(a, b, c.0, c.1);
+    if double_area.abs() < f64::EPSILON {
+        return;
+    }
+
+    let ((left, top), (right, bottom)) = *area;
+    let x_min = ((a.0.min(b.0).min(c.0) - EDGE_TOLERANCE).ceil() as i32).max(left.max(0));
+    let x_max = ((a.0.max(b.0).max(c.0) + EDGE_TOLERANCE).floor() as i32)
+        .min(right.min(depth_buffer.width as i32) - 1);
+    let y_min = ((a.1.min(b.1).min(c.1) - EDGE_TOLERANCE).ceil() as i32).max(top.max(0));
+    let y_max = ((a.1.max(b.1).max(c.1) + EDGE_TOLERANCE).floor() as i32)
+        .min(bottom.min(depth_buffer.height as i32) - 1);
+
+    for y in y_min..=y_max {
+        for x in x_min..=x_max {
+            let (px, py) = (x as f64, y as f64);
+            //barycentric coordinates, all of them are positive inside the triangle whatever its winding
+            let l0 = edge(b, c, px, py) / double_area;
+            let l1 = edge(c, a, px, py) / double_area;
+            let l2 = edge(a, b, px, py) / double_area;
+            if l0 < -EDGE_TOLERANCE || l1 < -EDGE_TOLERANCE || l2 < -EDGE_TOLERANCE {
+                continue;
+            }
+            let depth = l0 * a.2 + l1 * b.2 + l2 * c.2;
+            if depth > 1. || !depth_buffer.test_and_set(x as usize, y as usize, depth as f32) {
+                continue;
+            }
+
+            let (w0, w1, w2) = (l0 * a.3, l1 * b.3, l2 * c.3);
+            let total = w0 + w1 + w2;
+            let mut color: Rgba = [0; 4];
+            for (channel, value) in color.iter_mut().enumerate() {
+                let interpolated =
+                    (w0 * colors[0][channel] + w1 * colors[1][channel] + w2 * colors[2][channel])
+                        / total;
+                *value = interpolated.round().clamp(0., 255.) as u8;
+            }
+            image[y as usize][x as usize] = color;
+        }
+    }
+}
+
+//draw the faces of a mesh as solid triangles with a depth buffer the size of the image, and return both.
+//faces turned away from the viewer are skipped when the mesh culls back faces
+pub fn render_mesh(
+    image: Image,
+    mesh: &Mesh,
+    camera: Option<&Camera>,
+    viewport: Option<&WindowViewport>,
+) -> (Image, DepthBuffer) {
+    let mut new_image: Image = image.clone();
+    let mut depth_buffer = DepthBuffer::new(image[0].len(), image.len());
+    let (matrix, area) = view_setup(image[0].len(), image.len(), camera, viewport);
+    let front_faces = match mesh.cull_back_faces {
+        true => mesh.front_faces(&matrix),
+        false => vec![true; mesh.faces.len()],
+    };
+    let clip_vertices: Vec<ClipVertex> = mesh
+        .vertices
+        .iter()
+        .zip(mesh.colors.iter())
+        .map(|(vertex, color)| {
+            (
+                transform_point_homogeneous(*vertex, &matrix),
+                color.map(|channel| channel as f64),
+            )
+        })
+        .collect();
+
+    for ((a, b, c), front) in mesh.faces.iter().zip(front_faces) {
+        if !front {
+            continue;
+        }
+        let polygon = clip_near(&[clip_vertices[*a], clip_vertices[*b], clip_vertices[*c]]);
+        let screen: Vec<(f64, f64, f64, f64)> = polygon
+            .iter()
+            .map(|((x, y, z, w), _)| {
+                let (device_x, device_y) = ndc_to_device_exact(x / w, y / w, &area);
+                (device_x, device_y, z / w, 1. / w)
+            })
+            .collect();
+        for i in 1..polygon.len().saturating_sub(1) {
+            rasterize_triangle(
+                &mut new_image,
+                &mut depth_buffer,
+                [screen[0], screen[i], screen[i + 1]],
+                [polygon[0].1, polygon[i].1, polygon[i + 1].1],
+                &area,
+            );
+        }
+    }
+    (new_image, depth_buffer)
+}
+
+#[pymethods]
+impl DepthBuffer {
+    #[pyo3(name = "get")]
+    fn py_get(&self, x: usize, y: usize) -> PyResult<f32> {
+        if x >= self.width || y >= self.height {
+            return Err(PyIndexError::new_err(
+                "the pixel is outside of the depth buffer",
+            ));
+        }
+        Ok(self.get(x, y))
+    }
+
+    #[pyo3(name = "range")]
+    fn py_range(&self) -> Option<(f32, f32)> {
+        self.range()
+    }
+
+    //the depth row by row, as the image is
+    fn rows(&self) -> Vec<Vec<f32>> {
+        self.depth
+            .chunks(self.width.max(1))
+            .map(|row| row.to_vec())
+            .collect()
+    }
+}
+
+//a scene the other hidden surface renderers are checked against the depth buffer with
+#[cfg(test)]
+pub mod fixtures {
+    use super::*;
+
+    pub const PALETTE: [Rgba; 3] = [[255, 0, 0, 255], [0, 255, 0, 255], [0, 0, 255, 255]];
+
+    //two triangles going through each other, and a third one in front of part of both, one color each.
+    //the corners are off the pixel centers, so the renderers may fill the pixels on an edge their own way
+    pub fn crossing_triangles() -> Mesh {
+        let mut mesh = Mesh::from_triangles(
+            &[
+                [
+                    (5.3, 5.3, -20., 1.),
+                    (55.3, 5.3, -20., 1.),
+                    (30.3, 55.3, 20., 1.),
+                ],
+                [
+                    (5.3, 55.3, -20., 1.),
+                    (55.3, 55.3, -20., 1.),
+                    (30.3, 5.3, 20., 1.),
+                ],
+                [
+                    (20.3, 20.3, -40., 1.),
+                    (40.3, 20.3, -40., 1.),
+                    (30.3, 40.3, -40., 1.),
+                ],
+            ],
+            [0; 4],
+        );
+        //none of the triangles share a vertex, so each one has three of its own
+        mesh.colors = (0..9).map(|vertex| PALETTE[vertex / 3]).collect();
+        mesh
+    }
+
+    //draw the crossing triangles with a renderer, and count the pixels where it differs from the depth buffer
+    pub fn compare_with_depth_buffer(draw: impl Fn(Image, &Mesh) -> Image) -> (Image, usize) {
+        let mesh = crossing_triangles();
+        let image: Image = vec![vec![[255; 4]; 60]; 60];
+        let drawn = draw(image.clone(), &mesh);
+        let (z_buffered, _) = render_mesh(image, &mesh, None, None);
+        let different = drawn
+            .iter()
+            .flatten()
+            .zip(z_buffered.iter().flatten())
+            .filter(|(a, b)| a != b)
+            .count();
+        (drawn, different)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //a square of two triangles, in the pixels of the default view
+    fn square(corner: (f64, f64), size: f64, z: f64, color: Rgba) -> Mesh {
+        let (x, y) = corner;
+        Mesh::from_triangles(
+            &[
+                [
+                    (x, y, z, 1.),
+                    (x + size, y, z, 1.),
+                    (x + size, y + size, z, 1.),
+                ],
+                [
+                    (x, y, z, 1.),
+                    (x + size, y + size, z, 1.),
+                    (x, y + size, z, 1.),
+                ],
+            ],
+            color,
+        )
+    }
+
+    #[test]
+    fn closer_faces_win_whatever_the_order() {
+        let near = square((5., 5.), 20., -10., [255, 0, 0, 255]);
+        let far = square((15., 15.), 20., 10., [0, 0, 255, 255]);
+        let image: Image = vec![vec![[255; 4]; 40]; 40];
+        //the default view flips the rows, so y = 20 is row 19
+        for (first, second) in [(&near, &far), (&far, &near)] {
+            let mut mesh = first.clone();
+            let offset = mesh.vertices.len();
+            mesh.vertices.extend(second.vertices.iter());
+            mesh.colors.extend(second.colors.iter());
+            mesh.faces.extend(
+                second
+                    .faces
+                    .iter()
+                    .map(|(a, b, c)| (a + offset, b + offset, c + offset)),
+            );
+            let (image, _) = render_mesh(image.clone(), &mesh, None, None);
+            assert_eq!(image[19][20], [255, 0, 0, 255]);
+            assert_eq!(image[8][30], [0, 0, 255, 255]);
+        }
+    }
+
+    #[test]
+    fn triangles_sharing_an_edge_leave_no_gaps() {
+        let mesh = square((5., 5.), 20., 0., [255, 0, 0, 255]);
+        let (image, depth_buffer) = render_mesh(vec![vec![[255; 4]; 40]; 40], &mesh, None, None);
+        for row in image[14..=34].iter() {
+            assert!(row[5..=25].iter().all(|pixel| *pixel == [255, 0, 0, 255]));
+        }
+        assert_eq!(depth_buffer.range(), Some((0., 0.)));
+    }
+
+    #[test]
+    fn depth_images_span_the_range_drawn() {
+        let mut depth_buffer = DepthBuffer::new(3, 1);
+        assert_eq!(depth_buffer.range(), None);
+        assert!(depth_buffer.test_and_set(0, 0, 0.5));
+        assert!(!depth_buffer.test_and_set(0, 0, 0.7));
+        assert!(depth_buffer.test_and_set(1, 0, -0.5));
+        assert_eq!(depth_buffer.range(), Some((-0.5, 0.5)));
+
+        let gray = depth_buffer.to_image(None, None, &DepthColormap::Gray);
+        //near is white, far is black and the pixels nothing was drawn on are transparent
+        assert_eq!(
+            gray[0],
+            vec![[0, 0, 0, 255], [255, 255, 255, 255], [0, 0, 0, 0]]
+        );
+        let jet = depth_buffer.to_image(Some(-0.5), Some(1.5), &DepthColormap::Jet);
+        //the ends of the colormap are dark red and dark blue, with green in the middle
+        assert_eq!(jet[0][1], [128, 0, 0, 255]);
+        assert_eq!(jet[0][0], [128, 255, 128, 255]);
+    }
+}
@@ -0,0 +1,798 @@
+use crate::camera::Camera;
+use crate::common::{Edge, Image, Rgba, Vector3};
+use crate::light::Light;
+use crate::mesh::{point_to_vector, Mesh};
+use crate::operations::{transform_point_homogeneous, view_setup};
+use crate::transform::invert_matrix;
+use crate::vector::{add, cross, dot, length, normalize, scale, subtract};
+use crate::viewport::{device_to_ndc, WindowViewport};
+
+use ndarray::{ArrayBase, Dim, OwnedRepr};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+//how far along a ray a hit has to be to count, it keeps rays leaving a surface from hitting it again
+const RAY_EPSILON: f64 = 1e-6;
+
+//a color with channels from 0 to 1, so light can be added and scaled before it is stored in a pixel
+pub type Color = [f64; 3];
+
+pub fn to_color(rgba: &Rgba) -> Color {
+    [rgba[0], rgba[1], rgba[2]].map(|channel| channel as f64 / 255.)
+}
+
+pub fn to_rgba(color: &Color) -> Rgba {
+    let [r, g, b] = color.map(|channel| (channel.clamp(0., 1.) * 255.).round() as u8);
+    [r, g, b, 255]
+}
+
+fn add_weighted(color: &mut Color, other: &Color, weight: f64) {
+    for (channel, value) in color.iter_mut().zip(other) {
+        *channel += value * weight;
+    }
+}
+
+//the mirror direction of a direction arriving at a surface with the given normal
+pub fn reflect(direction: Vector3, normal: Vector3) -> Vector3 {
+    normalize(subtract(
+        direction,
+        scale(normal, 2. * dot(direction, normal)),
+    ))
+}
+
+//the direction of the light going through a surface with snell's law, where ratio is the refractive index
+//of the side it comes from over the one of the other side. the normal points to the side it comes from.
+//None when it is reflected back entirely
+pub fn refract(direction: Vector3, normal: Vector3, ratio: f64) -> Option<Vector3> {
+    let cosine = -dot(direction, normal);
+    let k = 1. - ratio * ratio * (1. - cosine * cosine);
+    if k < 0. {
+        return None;
+    }
+    Some(normalize(add(
+        scale(direction, ratio),
+        scale(normal, ratio * cosine - k.sqrt()),
+    )))
+}
+
+//the ratio of the refractive indexes for a ray entering the material, or leaving it
+pub fn refraction_ratio(material: &Material, entering: bool) -> f64 {
+    match entering {
+        true => 1. / material.refractive_index,
+        false => material.refractive_index,
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct Ray {
+    pub origin: Vector3,
+    //always normalized, so the parameter of a hit is its distance
+    pub direction: Vector3,
+}
+
+impl Ray {
+    pub fn at(&self, t: f64) -> Vector3 {
+        add(self.origin, scale(self.direction, t))
+    }
+}
+
+//how a surface reflects light. the weights of the local light, the mirror reflection and the refraction
+//are 1 - reflectivity - transparency, reflectivity and transparency
+#[pyclass]
+#[derive(Clone)]
+pub struct Material {
+    #[pyo3(get, set)]
+    pub diffuse: f64,
+    #[pyo3(get, set)]
+    pub specular: f64,
+    #[pyo3(get, set)]
+    pub shininess: f64,
+    #[pyo3(get, set)]
+    pub reflectivity: f64,
+    #[pyo3(get, set)]
+    pub transparency: f64,
+    //of the inside of the surface, the outside is taken as air
+    #[pyo3(get, set)]
+    pub refractive_index: f64,
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Material {
+            diffuse: 0.8,
+            specular: 0.2,
+            shininess: 32.,
+            reflectivity: 0.,
+            transparency: 0.,
+            refractive_index: 1.5,
+        }
+    }
+}
+
+#[pymethods]
+impl Material {
+    #[new]
+    #[args(
+        diffuse = "0.8",
+        specular = "0.2",
+        shininess = "32.",
+        reflectivity = "0.",
+        transparency = "0.",
+        refractive_index = "1.5"
+    )]
+    fn new(
+        diffuse: f64,
+        specular: f64,
+        shininess: f64,
+        reflectivity: f64,
+        transparency: f64,
+        refractive_index: f64,
+    ) -> PyResult<Self> {
+        if reflectivity < 0. || transparency < 0. || reflectivity + transparency > 1. {
+            return Err(PyValueError::new_err(
+                "reflectivity and transparency must be positive and add up to 1 at most",
+            ));
+        }
+        if refractive_index <= 0. {
+            return Err(PyValueError::new_err(
+                "the refractive index must be positive",
+            ));
+        }
+        Ok(Material {
+            diffuse,
+            specular,
+            shininess,
+            reflectivity,
+            transparency,
+            refractive_index,
+        })
+    }
+}
+
+#[derive(Clone)]
+struct Triangle {
+    corners: [Vector3; 3],
+    //the normals of the corners, interpolated over the triangle, or the normal of its plane
+    normals: [Vector3; 3],
+    colors: [Color; 3],
+}
+
+#[derive(Clone)]
+enum Shape {
+    Sphere {
+        center: Vector3,
+        radius: f64,
+        color: Color,
+    },
+    //an infinite plane, seen from both sides
+    Plane {
+        point: Vector3,
+        normal: Vector3,
+        color: Color,
+    },
+    //the triangles of a mesh, with the box around them so most rays skip them with a single test
+    Mesh {
+        triangles: Vec<Triangle>,
+        bounds: (Vector3, Vector3),
+    },
+}
+
+#[derive(Clone)]
+struct Object {
+    shape: Shape,
+    material: Material,
+}
+
+//where a ray meets an object. the normal points out of the object, whatever side the ray comes from
+pub struct Hit<'a> {
+    pub distance: f64,
+    pub point: Vector3,
+    pub normal: Vector3,
+    pub color: Color,
+    pub material: &'a Material,
+}
+
+fn intersect_sphere(ray: &Ray, center: Vector3, radius: f64) -> Option<f64> {
+    let to_origin = subtract(ray.origin, center);
+    let b = dot(to_origin, ray.direction);
+    let c = dot(to_origin, to_origin) - radius * radius;
+    let discriminant = b * b - c;
+    if discriminant < 0. {
+        return None;
+    }
+    let root = discriminant.sqrt();
+    [-b - root, -b + root]
+        .into_iter()
+        .find(|t| *t > RAY_EPSILON)
+}
+
+fn intersect_plane(ray: &Ray, point: Vector3, normal: Vector3) -> Option<f64> {
+    let facing = dot(normal, ray.direction);
+    if facing.abs() < f64::EPSILON {
+        return None;
+    }
+    let t = dot(subtract(point, ray.origin), normal) / facing;
+    (t > RAY_EPSILON).then_some(t)
+}
+
+//the distance and the barycentric coordinates of the second and third corners, with moller-trumbore
+fn intersect_triangle(ray: &Ray, corners: &[Vector3; 3]) -> Option<(f64, f64, f64)> {
+    let edge_1 = subtract(corners[1], corners[0]);
+    let edge_2 = subtract(corners[2], corners[0]);
+    let p = cross(ray.direction, edge_2);
+    let determinant = dot(edge_1, p);
+    if determinant.abs() < f64::EPSILON {
+        return None;
+    }
+    let to_origin = subtract(ray.origin, corners[0]);
+    let u = dot(to_origin, p) / determinant;
+    if !(0. ..=1.).contains(&u) {
+        return None;
+    }
+    let q = cross(to_origin, edge_1);
+    let v = dot(ray.direction, q) / determinant;
+    if v < 0. || u + v > 1. {
+        return None;
+    }
+    let t = dot(edge_2, q) / determinant;
+    (t > RAY_EPSILON).then_some((t, u, v))
+}
+
+//whether the ray goes through the box before the given distance, with the slab test
+fn hits_bounds(ray: &Ray, bounds: &(Vector3, Vector3), max_distance: f64) -> bool {
+    let (low, high) = bounds;
+    let mut t_enter: f64 = 0.;
+    let mut t_leave = max_distance;
+    for (origin, direction, low, high) in [
+        (ray.origin.0, ray.direction.0, low.0, high.0),
+        (ray.origin.1, ray.direction.1, low.1, high.1),
+        (ray.origin.2, ray.direction.2, low.2, high.2),
+    ] {
+        if direction.abs() < f64::EPSILON {
+            if origin < low || origin > high {
+                return false;
+            }
+            continue;
+        }
+        let (t0, t1) = ((low - origin) / direction, (high - origin) / direction);
+        t_enter = t_enter.max(t0.min(t1));
+        t_leave = t_leave.min(t0.max(t1));
+        if t_enter > t_leave {
+            return false;
+        }
+    }
+    true
+}
+
+impl Object {
+    fn intersect(&self, ray: &Ray, max_distance: f64) -> Option<Hit<'_>> {
+        let (distance, normal, color) = match &self.shape {
+            Shape::Sphere {
+                center,
+                radius,
+                color,
+            } => {
+                let t = intersect_sphere(ray, *center, *radius)?;
+                let normal = scale(subtract(ray.at(t), *center), 1. / radius);
+                (t, normal, *color)
+            }
+            Shape::Plane {
+                point,
+                normal,
+                color,
+            } => (intersect_plane(ray, *point, *normal)?, *normal, *color),
+            Shape::Mesh { triangles, bounds } => {
+                if !hits_bounds(ray, bounds, max_distance) {
+                    return None;
+                }
+                let (triangle, (t, u, v)) = triangles
+                    .iter()
+                    .filter_map(|triangle| {
+                        intersect_triangle(ray, &triangle.corners).map(|hit| (triangle, hit))
+                    })
+                    .min_by(|a, b| a.1 .0.total_cmp(&b.1 .0))?;
+                let weights = [1. - u - v, u, v];
+                let blend = |values: [Vector3; 3]| {
+                    (0..3).fold((0., 0., 0.), |sum, i| {
+                        add(sum, scale(values[i], weights[i]))
+                    })
+                };
+                let normal = normalize(blend(triangle.normals));
+                let color = triangle
+                    .colors
+                    .iter()
+                    .zip(weights)
+                    .fold([0.; 3], |sum, (color, weight)| {
+                        [0, 1, 2].map(|i| sum[i] + color[i] * weight)
+                    });
+                (t, normal, color)
+            }
+        };
+        if distance >= max_distance {
+            return None;
+        }
+        Some(Hit {
+            distance,
+            point: ray.at(distance),
+            normal,
+            color,
+            material: &self.material,
+        })
+    }
+}
+
+//a parallelogram that gives off light from its front side, the one cross(edge_1, edge_2) points to.
+//only the path tracer sees them
+#[derive(Clone)]
+pub struct AreaLight {
+    pub corner: Vector3,
+    pub edge_1: Vector3,
+    pub edge_2: Vector3,
+    pub normal: Vector3,
+    pub area: f64,
+    //the light given off by each point towards each direction in front of it
+    pub emission: Color,
+}
+
+impl AreaLight {
+    //the point at u and v of the way along the edges
+    pub fn point_at(&self, u: f64, v: f64) -> Vector3 {
+        add(
+            self.corner,
+            add(scale(self.edge_1, u), scale(self.edge_2, v)),
+        )
+    }
+
+    //the distance along a ray to the light, from either side
+    pub fn intersect(&self, ray: &Ray, max_distance: f64) -> Option<f64> {
+        let t = intersect_plane(ray, self.corner, self.normal)?;
+        if t >= max_distance {
+            return None;
+        }
+        //the coordinates of the point along the edges, with the normal before it is normalized
+        let offset = subtract(ray.at(t), self.corner);
+        let normal = cross(self.edge_1, self.edge_2);
+        let squared = dot(normal, normal);
+        let u = dot(cross(offset, self.edge_2), normal) / squared;
+        let v = dot(cross(self.edge_1, offset), normal) / squared;
+        ((0. ..=1.).contains(&u) && (0. ..=1.).contains(&v)).then_some(t)
+    }
+}
+
+//the objects and lights a ray tracer sees, all in world coordinates
+#[pyclass]
+#[derive(Clone)]
+pub struct Scene {
+    objects: Vec<Object>,
+    #[pyo3(get)]
+    pub lights: Vec<Light>,
+    pub area_lights: Vec<AreaLight>,
+    //light that reaches every point, shadowed or not
+    #[pyo3(get, set)]
+    pub ambient: f64,
+    //what the rays that leave the scene see
+    #[pyo3(get, set)]
+    pub background: Rgba,
+}
+
+impl Scene {
+    //the closest object along a ray, before max_distance
+    pub fn intersect(&self, ray: &Ray, max_distance: f64) -> Option<Hit<'_>> {
+        let mut closest: Option<Hit> = None;
+        for object in self.objects.iter() {
+            let limit = closest.as_ref().map_or(max_distance, |hit| hit.distance);
+            if let Some(hit) = object.intersect(ray, limit) {
+                closest = Some(hit);
+            }
+        }
+        closest
+    }
+
+    //the faces of a mesh as a single object, see add_mesh
+    pub fn push_mesh(&mut self, mesh: &Mesh, material: Option<Material>) {
+        let smooth = mesh.normals.len() == mesh.vertices.len();
+        let mut triangles: Vec<Triangle> = vec![];
+        for (a, b, c) in mesh.faces.iter() {
+            let corners = [*a, *b, *c].map(|index| point_to_vector(mesh.vertices[index]));
+            let face_normal = cross(
+                subtract(corners[1], corners[0]),
+                subtract(corners[2], corners[0]),
+            );
+            if length(face_normal) < f64::EPSILON {
+                continue;
+            }
+            let normals = match smooth {
+                true => [*a, *b, *c].map(|index| normalize(mesh.normals[index])),
+                false => [normalize(face_normal); 3],
+            };
+            triangles.push(Triangle {
+                corners,
+                normals,
+                colors: [*a, *b, *c].map(|index| to_color(&mesh.colors[index])),
+            });
+        }
+        let bounds = triangles.iter().flat_map(|triangle| triangle.corners).fold(
+            (
+                (f64::INFINITY, f64::INFINITY, f64::INFINITY),
+                (f64::NEG_INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY),
+            ),
+            |(low, high), point| {
+                (
+                    (low.0.min(point.0), low.1.min(point.1), low.2.min(point.2)),
+                    (
+                        high.0.max(point.0),
+                        high.1.max(point.1),
+                        high.2.max(point.2),
+                    ),
+                )
+            },
+        );
+        self.objects.push(Object {
+            shape: Shape::Mesh { triangles, bounds },
+            material: material.unwrap_or_default(),
+        });
+    }
+
+    //the closest area light along a ray, before max_distance, as its distance and the light
+    pub fn intersect_area_light(&self, ray: &Ray, max_distance: f64) -> Option<(f64, &AreaLight)> {
+        let mut closest: Option<(f64, &AreaLight)> = None;
+        for light in self.area_lights.iter() {
+            let limit = closest.map_or(max_distance, |(distance, _)| distance);
+            if let Some(distance) = light.intersect(ray, limit) {
+                closest = Some((distance, light));
+            }
+        }
+        closest
+    }
+
+    //whether an object is closer to a point than distance along the unit vector to_light,
+    //the lights themselves do not cast shadows
+    pub fn in_shadow(&self, point: Vector3, to_light: Vector3, distance: f64) -> bool {
+        let ray = Ray {
+            origin: point,
+            direction: to_light,
+        };
+        self.intersect(&ray, distance).is_some()
+    }
+
+    //the color seen along a ray. reflection and refraction rays are followed until depth reaches max_depth
+    pub fn trace(&self, ray: &Ray, depth: usize, max_depth: usize) -> Color {
+        match self.intersect(ray, f64::INFINITY) {
+            Some(hit) => self.shade(ray, &hit, depth, max_depth),
+            None => to_color(&self.background),
+        }
+    }
+
+    //the light leaving a hit towards the ray: ambient, diffuse and specular from the lights it sees,
+    //and what the mirror reflection and the refraction see
+    fn shade(&self, ray: &Ray, hit: &Hit, depth: usize, max_depth: usize) -> Color {
+        let material = hit.material;
+        let outside = dot(ray.direction, hit.normal) < 0.;
+        //the side of the surface the ray comes from, so surfaces are lit from both sides
+        let normal = match outside {
+            true => hit.normal,
+            false => scale(hit.normal, -1.),
+        };
+        let to_viewer = scale(ray.direction, -1.);
+
+        let mut local = hit.color.map(|channel| channel * self.ambient);
+        for light in self.lights.iter() {
+            let (to_light, intensity) = light.incoming(hit.point);
+            let diffuse = dot(normal, to_light);
+            if diffuse <= 0. || self.in_shadow(hit.point, to_light, light.distance(hit.point)) {
+                continue;
+            }
+            let reflected = subtract(scale(normal, 2. * diffuse), to_light);
+            let specular = dot(reflected, to_viewer).max(0.).powf(material.shininess);
+            for (channel, value) in local.iter_mut().enumerate() {
+                *value += intensity[channel]
+                    * (hit.color[channel] * material.diffuse * diffuse
+                        + material.specular * specular);
+            }
+        }
+        let surface = 1. - material.reflectivity - material.transparency;
+        let mut color = local.map(|channel| channel * surface);
+        if depth >= max_depth {
+            return color;
+        }
+
+        let mirror = Ray {
+            origin: hit.point,
+            direction: reflect(ray.direction, normal),
+        };
+        let mut reflected_weight = material.reflectivity;
+        if material.transparency > 0. {
+            match refract(ray.direction, normal, refraction_ratio(material, outside)) {
+                Some(direction) => {
+                    let refracted = Ray {
+                        origin: hit.point,
+                        direction,
+                    };
+                    let through = self.trace(&refracted, depth + 1, max_depth);
+                    add_weighted(&mut color, &through, material.transparency);
+                }
+                //total internal reflection, all the light that would go through is reflected
+                None => reflected_weight += material.transparency,
+            }
+        }
+        if reflected_weight > 0. {
+            let reflected = self.trace(&mirror, depth + 1, max_depth);
+            add_weighted(&mut color, &reflected, reflected_weight);
+        }
+        color
+    }
+}
+
+#[pymethods]
+impl Scene {
+    #[new]
+    #[args(ambient = "0.1", background = "[0, 0, 0, 255]")]
+    pub fn new(ambient: f64, background: Rgba) -> Self {
+        Scene {
+            objects: vec![],
+            lights: vec![],
+            area_lights: vec![],
+            ambient,
+            background,
+        }
+    }
+
+    #[getter]
+    fn object_count(&self) -> usize {
+        self.objects.len()
+    }
+
+    #[args(material = "None")]
+    fn add_sphere(
+        &mut self,
+        center: Vector3,
+        radius: f64,
+        color: Rgba,
+        material: Option<Material>,
+    ) -> PyResult<()> {
+        if radius <= 0. {
+            return Err(PyValueError::new_err("the radius must be positive"));
+        }
+        self.objects.push(Object {
+            shape: Shape::Sphere {
+                center,
+                radius,
+                color: to_color(&color),
+            },
+            material: material.unwrap_or_default(),
+        });
+        Ok(())
+    }
+
+    #[args(material = "None")]
+    fn add_plane(
+        &mut self,
+        point: Vector3,
+        normal: Vector3,
+        color: Rgba,
+        material: Option<Material>,
+    ) -> PyResult<()> {
+        if length(normal) == 0. {
+            return Err(PyValueError::new_err("the normal can not be zero"));
+        }
+        self.objects.push(Object {
+            shape: Shape::Plane {
+                point,
+                normal: normalize(normal),
+                color: to_color(&color),
+            },
+            material: material.unwrap_or_default(),
+        });
+        Ok(())
+    }
+
+    //the faces of a mesh, with the colors of its vertices. its vertex normals smooth the shading when it has them
+    #[args(material = "None")]
+    fn add_mesh(&mut self, mesh: &Mesh, material: Option<Material>) -> PyResult<()> {
+        if mesh.faces.is_empty() {
+            return Err(PyValueError::new_err("the mesh has no faces"));
+        }
+        self.push_mesh(mesh, material);
+        Ok(())
+    }
+
+    fn add_light(&mut self, light: Light) {
+        self.lights.push(light);
+    }
+
+    //a light for the path tracer, the parallelogram with a corner and two edges. it lights the side
+    //cross(edge_1, edge_2) points to, with its color times the intensity
+    #[args(color = "[255, 255, 255, 255]", intensity = "1.")]
+    fn add_area_light(
+        &mut self,
+        corner: Vector3,
+        edge_1: Vector3,
+        edge_2: Vector3,
+        color: Rgba,
+        intensity: f64,
+    ) -> PyResult<()> {
+        let normal = cross(edge_1, edge_2);
+        let area = length(normal);
+        if area < f64::EPSILON {
+            return Err(PyValueError::new_err(
+                "the edges of the light must not be parallel",
+            ));
+        }
+        self.area_lights.push(AreaLight {
+            corner,
+            edge_1,
+            edge_2,
+            normal: scale(normal, 1. / area),
+            area,
+            emission: to_color(&color).map(|channel| channel * intensity),
+        });
+        Ok(())
+    }
+}
+
+//the rays of a view, from the near side of the view volume of its clip space matrix towards the far side.
+//a camera gives perspective rays, and the other views parallel ones along +z, since a smaller z is closer
+pub struct ViewRays {
+    inverse: ArrayBase<OwnedRepr<f64>, Dim<[usize; 2]>>,
+    area: Edge,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl ViewRays {
+    //None when the view matrix can not be inverted
+    pub fn new(
+        width: usize,
+        height: usize,
+        camera: Option<&Camera>,
+        viewport: Option<&WindowViewport>,
+    ) -> Option<ViewRays> {
+        let (matrix, area) = view_setup(width, height, camera, viewport);
+        Some(ViewRays {
+            inverse: invert_matrix(&matrix)?,
+            area,
+            width,
+            height,
+        })
+    }
+
+    //the pixels of the image inside the area of the view, row by row
+    pub fn pixels(&self) -> Vec<(usize, usize)> {
+        let ((left, top), (right, bottom)) = self.area;
+        let mut pixels = vec![];
+        for y in top.max(0)..bottom.min(self.height as i32) {
+            for x in left.max(0)..right.min(self.width as i32) {
+                pixels.push((x as usize, y as usize));
+            }
+        }
+        pixels
+    }
+
+    //the ray through a point of the image, in pixels, the center of each pixel is at its integer coordinates
+    pub fn through(&self, x: f64, y: f64) -> Ray {
+        let (ndc_x, ndc_y) = device_to_ndc(x, y, &self.area);
+        let near = point_to_vector(transform_point_homogeneous(
+            (ndc_x, ndc_y, -1., 1.),
+            &self.inverse,
+        ));
+        let far = point_to_vector(transform_point_homogeneous(
+            (ndc_x, ndc_y, 1., 1.),
+            &self.inverse,
+        ));
+        Ray {
+            origin: near,
+            direction: normalize(subtract(far, near)),
+        }
+    }
+}
+
+//trace a ray through every pixel of the view. the pixels whose rays hit nothing are left as they are
+pub fn ray_trace(
+    image: Image,
+    scene: &Scene,
+    max_depth: usize,
+    camera: Option<&Camera>,
+    viewport: Option<&WindowViewport>,
+) -> Image {
+    let mut new_image: Image = image.clone();
+    let view = match ViewRays::new(image[0].len(), image.len(), camera, viewport) {
+        Some(view) => view,
+        None => return new_image,
+    };
+    for (x, y) in view.pixels() {
+        let ray = view.through(x as f64, y as f64);
+        if let Some(hit) = scene.intersect(&ray, f64::INFINITY) {
+            new_image[y][x] = to_rgba(&scene.shade(&ray, &hit, 0, max_depth));
+        }
+    }
+    new_image
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parallel_views_look_along_positive_z() {
+        let view = ViewRays::new(50, 40, None, None).unwrap();
+        let ray = view.through(10., 20.);
+        assert!((ray.direction.2 - 1.).abs() < 1e-9);
+        //the rows of the image go down while y goes up
+        assert!((ray.origin.0 - 10.).abs() < 1e-9 && (ray.origin.1 - 19.).abs() < 1e-9);
+        assert_eq!(view.pixels().len(), 50 * 40);
+    }
+
+    fn sphere(center: Vector3, radius: f64) -> Object {
+        Object {
+            shape: Shape::Sphere {
+                center,
+                radius,
+                color: [1.; 3],
+            },
+            material: Material::default(),
+        }
+    }
+
+    #[test]
+    fn rays_hit_the_closest_object() {
+        let along_z = Ray {
+            origin: (0., 0., -10.),
+            direction: (0., 0., 1.),
+        };
+        let mut scene = Scene::new(0.1, [0, 0, 0, 255]);
+        scene.objects.push(sphere((0., 0., 0.), 1.));
+        assert_eq!(
+            scene.intersect(&along_z, f64::INFINITY).unwrap().distance,
+            9.
+        );
+        //a ray starting inside the sphere leaves it on the far side
+        let inside = Ray {
+            origin: (0., 0., 0.),
+            direction: (0., 0., 1.),
+        };
+        assert_eq!(
+            scene.intersect(&inside, f64::INFINITY).unwrap().distance,
+            1.
+        );
+
+        let triangle = Mesh::from_triangles(
+            &[[(-2., -2., -5., 1.), (2., -2., -5., 1.), (0., 2., -5., 1.)]],
+            [255; 4],
+        );
+        scene.push_mesh(&triangle, None);
+        let hit = scene.intersect(&along_z, f64::INFINITY).unwrap();
+        assert_eq!((hit.distance, hit.normal), (5., (0., 0., 1.)));
+        assert!(scene.intersect(&along_z, 4.).is_none());
+        let shadowed = |light: Light| {
+            let point = (0., 0., 5.);
+            let (to_light, _) = light.incoming(point);
+            scene.in_shadow(point, to_light, light.distance(point))
+        };
+        assert!(shadowed(Light::point(
+            (0., 0., -20.),
+            [1.; 3],
+            (1., 0., 0.)
+        )));
+        assert!(!shadowed(Light::point(
+            (0., 20., 5.),
+            [1.; 3],
+            (1., 0., 0.)
+        )));
+        //directional lights have no position, their shadows reach any distance
+        assert!(shadowed(Light::directional((0., 0., 1.), [1.; 3])));
+        assert!(!shadowed(Light::directional((0., 0., -1.), [1.; 3])));
+    }
+
+    #[test]
+    fn refraction_bends_towards_the_normal_and_reflects_past_the_critical_angle() {
+        let normal = (0., 1., 0.);
+        let mirrored = reflect(normalize((1., -1., 0.)), normal);
+        assert!((dot(mirrored, normalize((1., 1., 0.))) - 1.).abs() < 1e-12);
+        let straight = refract((0., -1., 0.), normal, 1. / 1.5).unwrap();
+        assert!((straight.1 + 1.).abs() < 1e-12);
+        //entering glass at 45 degrees, sin(out) = sin(45) / 1.5
+        let bent = refract(normalize((1., -1., 0.)), normal, 1. / 1.5).unwrap();
+        assert!((bent.0 - 0.5_f64.sqrt() / 1.5).abs() < 1e-12);
+        assert!(refract(normalize((1., -1., 0.)), normal, 1.5).is_none());
+    }
+}
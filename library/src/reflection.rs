@@ -0,0 +1,77 @@
+use crate::common::Vector3;
+use crate::vector::{dot, normalize};
+
+use ndarray::{arr2, ArrayBase, Dim, OwnedRepr};
+
+//mirror through the plane that goes through the point with the given normal
+pub fn reflection_matrix(
+    point: Vector3,
+    normal: Vector3,
+) -> ArrayBase<OwnedRepr<f64>, Dim<[usize; 2]>> {
+    let (x, y, z) = normalize(normal);
+    //p' = p - 2 * ((p - point) . n) * n, so the plane offset ends up in the translation
+    let offset = 2. * dot(point, (x, y, z));
+    // rustfmt-ignore
+    let matrix: [[f64; 4]; 4] = [
+        [1. - 2. * x * x, -2. * x * y, -2. * x * z, 0.],
+        [-2. * x * y, 1. - 2. * y * y, -2. * y * z, 0.],
+        [-2. * x * z, -2. * y * z, 1. - 2. * z * z, 0.],
+        [offset * x, offset * y, offset * z, 1.],
+    ];
+    arr2(&matrix)
+}
+
+//normal of one of the coordinate planes, given by the two axes it contains
+pub fn coordinate_plane_normal(plane: &str) -> Option<Vector3> {
+    match plane {
+        "xy" | "yx" => Some((0., 0., 1.)),
+        "yz" | "zy" => Some((1., 0., 0.)),
+        "xz" | "zx" => Some((0., 1., 0.)),
+        _ => None,
+    }
+}
+
+//mirror the xy plane across the line that goes through the point in the given direction.
+//it is the same as mirroring through the plane that holds the line and is parallel to z
+pub fn line_reflection_matrix_2d(
+    point: (f64, f64),
+    direction: (f64, f64),
+) -> ArrayBase<OwnedRepr<f64>, Dim<[usize; 2]>> {
+    reflection_matrix((point.0, point.1, 0.), (-direction.1, direction.0, 0.))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray::arr1;
+
+    fn reflect(matrix: &ArrayBase<OwnedRepr<f64>, Dim<[usize; 2]>>, point: Vector3) -> Vector3 {
+        let product = arr1(&[point.0, point.1, point.2, 1.]).dot(matrix);
+        (product[0], product[1], product[2])
+    }
+
+    fn close(a: Vector3, b: Vector3) -> bool {
+        (a.0 - b.0).abs() < 1e-9 && (a.1 - b.1).abs() < 1e-9 && (a.2 - b.2).abs() < 1e-9
+    }
+
+    #[test]
+    fn planes_mirror_points_to_the_other_side() {
+        let matrix = reflection_matrix((0., 0., 5.), coordinate_plane_normal("yx").unwrap());
+        assert!(close(reflect(&matrix, (1., 2., 8.)), (1., 2., 2.)));
+        //points on the plane stay where they are, and mirroring twice gives the identity
+        let matrix = reflection_matrix((1., 1., 1.), (1., 2., -1.));
+        assert!(close(reflect(&matrix, (1., 1., 1.)), (1., 1., 1.)));
+        assert!(close(
+            reflect(&matrix, reflect(&matrix, (4., -3., 2.))),
+            (4., -3., 2.)
+        ));
+    }
+
+    #[test]
+    fn lines_mirror_the_xy_plane() {
+        //the diagonal y = x + 1 swaps the coordinates around it
+        let matrix = line_reflection_matrix_2d((0., 1.), (1., 1.));
+        assert!(close(reflect(&matrix, (2., 0., 7.)), (-1., 3., 7.)));
+        assert!(coordinate_plane_normal("xw").is_none());
+    }
+}
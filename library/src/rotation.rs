@@ -0,0 +1,437 @@
+use crate::common::Vector3;
+use crate::operations::translation_matrix_3d;
+use crate::transform::Transform;
+use crate::vector::{cross, dot, length, normalize};
+
+use ndarray::{arr2, ArrayBase, Dim, OwnedRepr};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+//rotations about arbitrary axes and quaternion orientations.
+//angles follow the same direction as rotation_matrix_3d does for 'x' and 'z', so a rotation of some degrees
+//around (1, 0, 0) is the same as rotating those degrees around 'x'. 'y' turns the other way, a rotation
+//around (0, 1, 0) is the same as rotating the opposite degrees around 'y'
+
+pub fn check_axis(axis: char) -> PyResult<()> {
+    match axis {
+        'x' | 'y' | 'z' => Ok(()),
+        _ => Err(PyValueError::new_err(format!(
+            "the rotation axis must be 'x', 'y' or 'z', got '{}'",
+            axis
+        ))),
+    }
+}
+
+//index of each axis of an euler order like "xyz", where the first axis is the first rotation made
+fn parse_euler_order(order: &str) -> PyResult<[usize; 3]> {
+    let mut indexes = [0; 3];
+    let mut seen = [false; 3];
+    let chars: Vec<char> = order.chars().collect();
+    if chars.len() == 3 {
+        for (i, axis) in chars.iter().enumerate() {
+            let index = match axis {
+                'x' => 0,
+                'y' => 1,
+                'z' => 2,
+                _ => break,
+            };
+            indexes[i] = index;
+            seen[index] = true;
+        }
+    }
+    if seen.iter().all(|s| *s) {
+        Ok(indexes)
+    } else {
+        Err(PyValueError::new_err(format!(
+            "the euler order must use each of x, y and z once, got '{}'",
+            order
+        )))
+    }
+}
+
+//rotation of some degrees around an axis that goes through the pivot, using rodrigues' formula
+pub fn axis_angle_matrix(
+    degrees: f64,
+    axis: Vector3,
+    pivot: Vector3,
+) -> ArrayBase<OwnedRepr<f64>, Dim<[usize; 2]>> {
+    let (x, y, z) = normalize(axis);
+    let (sin, cos) = degrees.to_radians().sin_cos();
+    let t = 1. - cos;
+    // rustfmt-ignore
+    let matrix: [[f64; 4]; 4] = [
+        [
+            t * x * x + cos,
+            t * x * y - sin * z,
+            t * x * z + sin * y,
+            0.,
+        ],
+        [
+            t * x * y + sin * z,
+            t * y * y + cos,
+            t * y * z - sin * x,
+            0.,
+        ],
+        [
+            t * x * z - sin * y,
+            t * y * z + sin * x,
+            t * z * z + cos,
+            0.,
+        ],
+        [0., 0., 0., 1.],
+    ];
+    translation_matrix_3d(-pivot.0, -pivot.1, -pivot.2)
+        .dot(&arr2(&matrix))
+        .dot(&translation_matrix_3d(pivot.0, pivot.1, pivot.2))
+}
+
+//a unit quaternion. internally it holds the rotation the points actually go through,
+//while every angle it receives or returns follows the direction used by the rest of the library
+#[pyclass]
+#[derive(Clone)]
+pub struct Quaternion {
+    #[pyo3(get)]
+    pub w: f64,
+    #[pyo3(get)]
+    pub x: f64,
+    #[pyo3(get)]
+    pub y: f64,
+    #[pyo3(get)]
+    pub z: f64,
+}
+
+impl Quaternion {
+    fn normalized(&self) -> Quaternion {
+        let norm = (self.w * self.w + self.x * self.x + self.y * self.y + self.z * self.z).sqrt();
+        if norm == 0. {
+            return Quaternion::identity();
+        }
+        Quaternion {
+            w: self.w / norm,
+            x: self.x / norm,
+            y: self.y / norm,
+            z: self.z / norm,
+        }
+    }
+
+    fn product(a: &Quaternion, b: &Quaternion) -> Quaternion {
+        Quaternion {
+            w: a.w * b.w - a.x * b.x - a.y * b.y - a.z * b.z,
+            x: a.w * b.x + a.x * b.w + a.y * b.z - a.z * b.y,
+            y: a.w * b.y - a.x * b.z + a.y * b.w + a.z * b.x,
+            z: a.w * b.z + a.x * b.y - a.y * b.x + a.z * b.w,
+        }
+    }
+
+    //rotation matrix for column vectors, in the usual right handed direction
+    fn column_matrix(&self) -> [[f64; 3]; 3] {
+        let (w, x, y, z) = (self.w, self.x, self.y, self.z);
+        [
+            [
+                1. - 2. * (y * y + z * z),
+                2. * (x * y - w * z),
+                2. * (x * z + w * y),
+            ],
+            [
+                2. * (x * y + w * z),
+                1. - 2. * (x * x + z * z),
+                2. * (y * z - w * x),
+            ],
+            [
+                2. * (x * z - w * y),
+                2. * (y * z + w * x),
+                1. - 2. * (x * x + y * y),
+            ],
+        ]
+    }
+
+    //rotation matrix for the row vectors used by the library, around the pivot
+    pub fn matrix(&self, pivot: Vector3) -> ArrayBase<OwnedRepr<f64>, Dim<[usize; 2]>> {
+        let r = self.column_matrix();
+        // rustfmt-ignore
+        let matrix: [[f64; 4]; 4] = [
+            [r[0][0], r[1][0], r[2][0], 0.],
+            [r[0][1], r[1][1], r[2][1], 0.],
+            [r[0][2], r[1][2], r[2][2], 0.],
+            [0., 0., 0., 1.],
+        ];
+        translation_matrix_3d(-pivot.0, -pivot.1, -pivot.2)
+            .dot(&arr2(&matrix))
+            .dot(&translation_matrix_3d(pivot.0, pivot.1, pivot.2))
+    }
+
+    pub fn identity() -> Quaternion {
+        Quaternion {
+            w: 1.,
+            x: 0.,
+            y: 0.,
+            z: 0.,
+        }
+    }
+
+    pub fn from_axis_angle(axis: Vector3, degrees: f64) -> Quaternion {
+        let (x, y, z) = normalize(axis);
+        //the library turns the opposite way of the right hand rule
+        let (sin, cos) = (-degrees.to_radians() / 2.).sin_cos();
+        Quaternion {
+            w: cos,
+            x: x * sin,
+            y: y * sin,
+            z: z * sin,
+        }
+    }
+
+    //this rotation followed by the other one
+    pub fn then(&self, other: &Quaternion) -> Quaternion {
+        Quaternion::product(other, self).normalized()
+    }
+
+    pub fn slerp(&self, other: &Quaternion, t: f64) -> Quaternion {
+        let mut other = other.clone();
+        let mut cos = self.w * other.w + self.x * other.x + self.y * other.y + self.z * other.z;
+        //q and -q are the same rotation, the shortest path goes to the closest one
+        if cos < 0. {
+            other = Quaternion {
+                w: -other.w,
+                x: -other.x,
+                y: -other.y,
+                z: -other.z,
+            };
+            cos = -cos;
+        }
+        let (weight_self, weight_other) = if cos > 1. - 1e-9 {
+            //almost the same orientation, a linear interpolation avoids dividing by sin(0)
+            (1. - t, t)
+        } else {
+            let angle = cos.acos();
+            let sin = angle.sin();
+            (((1. - t) * angle).sin() / sin, (t * angle).sin() / sin)
+        };
+        Quaternion {
+            w: weight_self * self.w + weight_other * other.w,
+            x: weight_self * self.x + weight_other * other.x,
+            y: weight_self * self.y + weight_other * other.y,
+            z: weight_self * self.z + weight_other * other.z,
+        }
+        .normalized()
+    }
+
+    //euler angles in degrees, one for each axis of the order, with the first one applied first
+    pub fn from_euler_order(angles: Vector3, order: [usize; 3]) -> Quaternion {
+        let axes = [(1., 0., 0.), (0., 1., 0.), (0., 0., 1.)];
+        let angles = [angles.0, angles.1, angles.2];
+        let mut quaternion = Quaternion::identity();
+        for (axis, angle) in order.iter().zip(angles.iter()) {
+            quaternion = quaternion.then(&Quaternion::from_axis_angle(axes[*axis], *angle));
+        }
+        quaternion
+    }
+
+    pub fn to_euler_order(&self, order: [usize; 3]) -> Vector3 {
+        euler_from_column_matrix(&self.column_matrix(), order)
+    }
+}
+
+//euler angles in degrees of a rotation matrix for column vectors, one for each axis of the order
+pub fn euler_from_column_matrix(r: &[[f64; 3]; 3], order: [usize; 3]) -> Vector3 {
+    let [a, b, c] = order;
+    //in the basis (a, b, c) the rotation is made around x, then y, then z.
+    //odd orders make that basis left handed, which flips the direction of the angles
+    let q = |i: usize, j: usize| r[order[i]][order[j]];
+    let sign = if (a + 1) % 3 == b && (b + 1) % 3 == c {
+        1.
+    } else {
+        -1.
+    };
+    let sin_b = (-q(2, 0)).clamp(-1., 1.);
+    let angle_b = sin_b.asin();
+    let (angle_a, angle_c) = if sin_b.abs() < 1. - 1e-9 {
+        (q(2, 1).atan2(q(2, 2)), q(1, 0).atan2(q(0, 0)))
+    } else {
+        //gimbal lock, the first and last rotations share the same axis so all of it goes to the last one
+        (0., (-q(0, 1)).atan2(q(1, 1)))
+    };
+    //back to the direction used by the library
+    (
+        -sign * angle_a.to_degrees(),
+        -sign * angle_b.to_degrees(),
+        -sign * angle_c.to_degrees(),
+    )
+}
+
+#[pymethods]
+impl Quaternion {
+    #[new]
+    #[args(w = "1.", x = "0.", y = "0.", z = "0.")]
+    fn new(w: f64, x: f64, y: f64, z: f64) -> Self {
+        Quaternion { w, x, y, z }.normalized()
+    }
+
+    #[staticmethod]
+    #[pyo3(name = "from_axis_angle")]
+    fn py_from_axis_angle(axis: Vector3, degrees: f64) -> PyResult<Quaternion> {
+        if length(axis) == 0. {
+            return Err(PyValueError::new_err("the rotation axis can not be zero"));
+        }
+        Ok(Quaternion::from_axis_angle(axis, degrees))
+    }
+
+    //the rotation that takes the direction of a to the direction of b, as used by an arcball
+    #[staticmethod]
+    fn between(a: Vector3, b: Vector3) -> Quaternion {
+        let (a, b) = (normalize(a), normalize(b));
+        let axis = cross(a, b);
+        let angle = dot(a, b).clamp(-1., 1.).acos().to_degrees();
+        if length(axis) < 1e-12 {
+            if dot(a, b) > 0. {
+                return Quaternion::identity();
+            }
+            //opposite directions, any axis perpendicular to a works
+            let helper = if a.0.abs() < 0.9 {
+                (1., 0., 0.)
+            } else {
+                (0., 1., 0.)
+            };
+            return Quaternion::from_axis_angle(cross(a, helper), -180.);
+        }
+        //the points turn the right handed way, which is a negative angle for the library
+        Quaternion::from_axis_angle(axis, -angle)
+    }
+
+    #[staticmethod]
+    #[args(order = "\"xyz\"")]
+    fn from_euler(angles: Vector3, order: &str) -> PyResult<Quaternion> {
+        Ok(Quaternion::from_euler_order(
+            angles,
+            parse_euler_order(order)?,
+        ))
+    }
+
+    #[args(order = "\"xyz\"")]
+    fn to_euler(&self, order: &str) -> PyResult<Vector3> {
+        Ok(self.to_euler_order(parse_euler_order(order)?))
+    }
+
+    //returns (axis, degrees)
+    fn to_axis_angle(&self) -> (Vector3, f64) {
+        let sin = (1. - self.w * self.w).max(0.).sqrt();
+        if sin < 1e-12 {
+            return ((1., 0., 0.), 0.);
+        }
+        let angle = 2. * self.w.clamp(-1., 1.).acos();
+        (
+            (self.x / sin, self.y / sin, self.z / sin),
+            -angle.to_degrees(),
+        )
+    }
+
+    #[pyo3(name = "then")]
+    fn py_then(&self, other: &Quaternion) -> Quaternion {
+        self.then(other)
+    }
+
+    #[pyo3(name = "slerp")]
+    fn py_slerp(&self, other: &Quaternion, t: f64) -> Quaternion {
+        self.slerp(other, t)
+    }
+
+    fn inverse(&self) -> Quaternion {
+        Quaternion {
+            w: self.w,
+            x: -self.x,
+            y: -self.y,
+            z: -self.z,
+        }
+    }
+
+    #[args(pivot = "(0., 0., 0.)")]
+    fn to_transform(&self, pivot: Vector3) -> Transform {
+        Transform::from_matrix(self.matrix(pivot))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::operations::{rotation_matrix_3d, transform_point_homogeneous};
+
+    fn close(
+        a: &ArrayBase<OwnedRepr<f64>, Dim<[usize; 2]>>,
+        b: &ArrayBase<OwnedRepr<f64>, Dim<[usize; 2]>>,
+    ) -> bool {
+        a.iter().zip(b.iter()).all(|(a, b)| (a - b).abs() < 1e-9)
+    }
+
+    #[test]
+    fn axis_rotations_keep_their_directions() {
+        let rotate = |axis: char, point: (f64, f64, f64, f64)| {
+            let p = transform_point_homogeneous(point, &rotation_matrix_3d(90., axis));
+            (p.0.round(), p.1.round(), p.2.round())
+        };
+        assert_eq!(rotate('x', (0., 1., 0., 1.)), (0., 0., -1.));
+        assert_eq!(rotate('y', (1., 0., 0., 1.)), (0., 0., -1.));
+        assert_eq!(rotate('z', (1., 0., 0., 1.)), (0., -1., 0.));
+    }
+
+    #[test]
+    fn axis_angle_matches_the_axis_rotations() {
+        let origin = (0., 0., 0.);
+        assert!(close(
+            &axis_angle_matrix(30., (1., 0., 0.), origin),
+            &rotation_matrix_3d(30., 'x')
+        ));
+        assert!(close(
+            &axis_angle_matrix(30., (0., 1., 0.), origin),
+            &rotation_matrix_3d(-30., 'y')
+        ));
+        assert!(close(
+            &axis_angle_matrix(30., (0., 0., 2.), origin),
+            &rotation_matrix_3d(30., 'z')
+        ));
+    }
+
+    #[test]
+    fn quaternions_match_axis_angle() {
+        let (axis, pivot) = ((1., 2., -0.5), (3., 0., 1.));
+        let quaternion = Quaternion::from_axis_angle(axis, 70.);
+        assert!(close(
+            &quaternion.matrix(pivot),
+            &axis_angle_matrix(70., axis, pivot)
+        ));
+        let (turn_axis, degrees) = quaternion.to_axis_angle();
+        assert!((degrees.abs() - 70.).abs() < 1e-9);
+        let turn = Quaternion::from_axis_angle(turn_axis, degrees);
+        assert!(close(&turn.matrix(pivot), &quaternion.matrix(pivot)));
+    }
+
+    #[test]
+    fn euler_angles_round_trip_in_every_order() {
+        let angles = (20., -35., 50.);
+        let orders = [
+            [0, 1, 2],
+            [0, 2, 1],
+            [1, 0, 2],
+            [1, 2, 0],
+            [2, 0, 1],
+            [2, 1, 0],
+        ];
+        for order in orders {
+            let back = Quaternion::from_euler_order(angles, order).to_euler_order(order);
+            assert!((back.0 - angles.0).abs() < 1e-9, "{:?}", order);
+            assert!((back.1 - angles.1).abs() < 1e-9, "{:?}", order);
+            assert!((back.2 - angles.2).abs() < 1e-9, "{:?}", order);
+        }
+    }
+
+    #[test]
+    fn slerp_goes_halfway() {
+        let start = Quaternion::identity();
+        let end = Quaternion::from_axis_angle((0., 0., 1.), 90.);
+        let middle = start.slerp(&end, 0.5);
+        let expected = Quaternion::from_axis_angle((0., 0., 1.), 45.);
+        assert!(close(
+            &middle.matrix((0., 0., 0.)),
+            &expected.matrix((0., 0., 0.))
+        ));
+    }
+}
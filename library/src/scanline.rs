@@ -0,0 +1,193 @@
+use crate::camera::Camera;
+use crate::common::{Image, Rgba};
+use crate::mesh::Mesh;
+use crate::painter::{convex_span, project_faces, DepthPolygon};
+use crate::viewport::WindowViewport;
+
+//a polygon crossing the current scanline, with the part of the row it covers
+struct ActiveSpan<'a> {
+    polygon: &'a DepthPolygon,
+    x_start: f64,
+    x_end: f64,
+}
+
+//the polygon closest to the viewer at the start of an interval of the row, and where another one
+//comes in front of it, None when it stays the closest until the end
+fn closest_in_interval(spans: &[&ActiveSpan], y: f64, start: f64, end: f64) -> (Rgba, Option<f64>) {
+    let depth = |span: &ActiveSpan, x: f64| span.polygon.depth_at(x, y).unwrap_or(f64::INFINITY);
+    //ties at the start are broken by the depth at the end, which is the polygon in front right after it
+    let closest = spans
+        .iter()
+        .min_by(|a, b| {
+            depth(a, start)
+                .total_cmp(&depth(b, start))
+                .then(depth(a, end).total_cmp(&depth(b, end)))
+        })
+        .unwrap();
+
+    let mut crossing: Option<f64> = None;
+    for span in spans.iter() {
+        //the depths change linearly along the row, so they cross at most once
+        let d_start = depth(span, start) - depth(closest, start);
+        let d_end = depth(span, end) - depth(closest, end);
+        if d_end < 0. && d_start >= 0. {
+            let x = start + (end - start) * d_start / (d_start - d_end);
+            if x > start && !matches!(crossing, Some(crossing) if x >= crossing) {
+                crossing = Some(x);
+            }
+        }
+    }
+    (closest.polygon.color, crossing)
+}
+
+//draw the faces of a mesh one scanline at a time, the way watkins' algorithm does. the polygons crossing each
+//row are kept in an active list, the row is cut where their spans start and end, and the depth is resolved
+//for each of those intervals, splitting them again where two polygons go through each other.
+//only a row of depths is needed at a time, there is no depth buffer
+pub fn draw_mesh_scanline(
+    image: Image,
+    mesh: &Mesh,
+    camera: Option<&Camera>,
+    viewport: Option<&WindowViewport>,
+) -> Image {
+    let mut new_image: Image = image.clone();
+    let (width, height) = (image[0].len() as i32, image.len() as i32);
+
+    //polygons seen edge-on cover no pixels and have no depth
+    let mut polygons: Vec<(DepthPolygon, f64, f64)> =
+        project_faces(mesh, image[0].len(), image.len(), camera, viewport)
+            .into_iter()
+            .filter(|polygon| polygon.depth_at(0., 0.).is_some())
+            .map(|polygon| {
+                let (y_min, y_max) = polygon.extent(1);
+                (polygon, y_min, y_max)
+            })
+            .collect();
+    polygons.sort_by(|a, b| a.1.total_cmp(&b.1));
+
+    let mut next_polygon = 0;
+    let mut active: Vec<usize> = vec![];
+    for y in 0..height {
+        let row = y as f64;
+        while next_polygon < polygons.len() && polygons[next_polygon].1 <= row {
+            active.push(next_polygon);
+            next_polygon += 1;
+        }
+        active.retain(|index| polygons[*index].2 >= row);
+        if active.is_empty() {
+            continue;
+        }
+
+        let spans: Vec<ActiveSpan> = active
+            .iter()
+            .filter_map(|index| {
+                let polygon = &polygons[*index].0;
+                convex_span(&polygon.outline(), row).map(|(x_start, x_end)| ActiveSpan {
+                    polygon,
+                    x_start,
+                    x_end,
+                })
+            })
+            .collect();
+        let mut breaks: Vec<f64> = spans
+            .iter()
+            .flat_map(|span| [span.x_start, span.x_end])
+            .collect();
+        breaks.sort_by(|a, b| a.total_cmp(b));
+        breaks.dedup();
+
+        //the pixel centers inside each interval, the ones on a break are left for later
+        for interval in breaks.windows(2) {
+            let (mut start, end) = (interval[0], interval[1]);
+            let middle = (start + end) / 2.;
+            let covering: Vec<&ActiveSpan> = spans
+                .iter()
+                .filter(|span| span.x_start <= middle && middle <= span.x_end)
+                .collect();
+            //a gap between polygons
+            if covering.is_empty() {
+                continue;
+            }
+            let mut first = (start.floor() as i32 + 1).max(0);
+            while start < end {
+                let (color, crossing) = closest_in_interval(&covering, row, start, end);
+                let last = match crossing {
+                    Some(crossing) => crossing.floor() as i32,
+                    None => end.ceil() as i32 - 1,
+                };
+                for x in first..=last.min(width - 1) {
+                    new_image[y as usize][x as usize] = color;
+                }
+                first = first.max(last + 1);
+                start = crossing.unwrap_or(end);
+            }
+        }
+        //a pixel center on a break takes the closest of the polygons whose spans reach it
+        for x in breaks.iter().filter(|x| x.fract() == 0.) {
+            if *x < 0. || *x >= width as f64 {
+                continue;
+            }
+            let closest = spans
+                .iter()
+                .filter(|span| span.x_start <= *x && *x <= span.x_end)
+                .filter_map(|span| span.polygon.depth_at(*x, row).map(|depth| (depth, span)))
+                .min_by(|a, b| a.0.total_cmp(&b.0));
+            if let Some((_, span)) = closest {
+                new_image[y as usize][*x as usize] = span.polygon.color;
+            }
+        }
+    }
+    new_image
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::raster::fixtures::{compare_with_depth_buffer, PALETTE};
+
+    #[test]
+    fn scanline_matches_the_depth_buffer() {
+        let (drawn, different) =
+            compare_with_depth_buffer(|image, mesh| draw_mesh_scanline(image, mesh, None, None));
+        for color in PALETTE {
+            assert!(
+                drawn
+                    .iter()
+                    .flatten()
+                    .filter(|pixel| **pixel == color)
+                    .count()
+                    > 50
+            );
+        }
+        //rounding may still give a pixel along an edge to different polygons
+        assert!(different < 10, "{} pixels are different", different);
+    }
+
+    #[test]
+    fn intervals_are_split_where_the_polygons_cross() {
+        //the depth of one square grows with x and the depth of the other one falls, they cross at x = 5
+        let rising = DepthPolygon::new(
+            vec![(0., 0., 0.), (10., 0., 10.), (10., 10., 10.), (0., 10., 0.)],
+            [255, 0, 0, 255],
+        )
+        .unwrap();
+        let falling = DepthPolygon::new(
+            vec![(0., 0., 10.), (10., 0., 0.), (10., 10., 0.), (0., 10., 10.)],
+            [0, 0, 255, 255],
+        )
+        .unwrap();
+        let spans = [&rising, &falling].map(|polygon| ActiveSpan {
+            polygon,
+            x_start: 0.,
+            x_end: 10.,
+        });
+        let covering: Vec<&ActiveSpan> = spans.iter().collect();
+        let (color, crossing) = closest_in_interval(&covering, 5., 0., 10.);
+        assert_eq!(color, [255, 0, 0, 255]);
+        assert!((crossing.unwrap() - 5.).abs() < 1e-9);
+        assert_eq!(
+            closest_in_interval(&covering, 5., 5., 10.),
+            ([0, 0, 255, 255], None)
+        );
+    }
+}
@@ -0,0 +1,226 @@
+use crate::common::HomogeneousPoint;
+use crate::mesh::{point_to_vector, Mesh, WIREFRAME_COLOR};
+
+use std::fs;
+use std::io::{Error, ErrorKind, Result};
+
+const HEADER_SIZE: usize = 80;
+const TRIANGLE_SIZE: usize = 50;
+
+fn invalid_data(message: String) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
+
+//binary stl: an 80 bytes header, the number of triangles and, for each triangle, the normal, the three corners
+//as little endian f32 and two bytes of attributes
+fn parse_binary_stl(bytes: &[u8]) -> Result<Vec<[HomogeneousPoint; 3]>> {
+    let count =
+        u32::from_le_bytes(bytes[HEADER_SIZE..HEADER_SIZE + 4].try_into().unwrap()) as usize;
+    if bytes.len() < HEADER_SIZE + 4 + count * TRIANGLE_SIZE {
+        return Err(invalid_data(format!(
+            "expected {} triangles, the file is too short",
+            count
+        )));
+    }
+
+    let read_f32 =
+        |offset: usize| f32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap()) as f64;
+    let mut triangles = vec![];
+    for i in 0..count {
+        //skip the normal, the mesh computes its own
+        let start = HEADER_SIZE + 4 + i * TRIANGLE_SIZE + 12;
+        let mut triangle = [(0., 0., 0., 1.); 3];
+        for (j, corner) in triangle.iter_mut().enumerate() {
+            let offset = start + j * 12;
+            *corner = (
+                read_f32(offset),
+                read_f32(offset + 4),
+                read_f32(offset + 8),
+                1.,
+            );
+        }
+        triangles.push(triangle);
+    }
+    Ok(triangles)
+}
+
+//ascii stl: "solid" and facets with an outer loop of three vertices, the normals are ignored
+fn parse_ascii_stl(content: &str) -> Result<Vec<[HomogeneousPoint; 3]>> {
+    if content.split_whitespace().next() != Some("solid") {
+        return Err(invalid_data("an ascii stl starts with solid".to_string()));
+    }
+    let mut triangles = vec![];
+    let mut corners: Vec<HomogeneousPoint> = vec![];
+
+    for (line_number, line) in content.lines().enumerate() {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        match tokens.first() {
+            Some(&"vertex") => {
+                let numbers: Vec<f64> = tokens[1..]
+                    .iter()
+                    .filter_map(|value| value.parse().ok())
+                    .collect();
+                if numbers.len() != 3 {
+                    return Err(invalid_data(format!(
+                        "line {}: a vertex needs three values",
+                        line_number + 1
+                    )));
+                }
+                corners.push((numbers[0], numbers[1], numbers[2], 1.));
+            }
+            Some(&"endloop") => {
+                if corners.len() != 3 {
+                    return Err(invalid_data(format!(
+                        "line {}: a facet needs three vertices",
+                        line_number + 1
+                    )));
+                }
+                triangles.push([corners[0], corners[1], corners[2]]);
+                corners.clear();
+            }
+            _ => {}
+        }
+    }
+    if !corners.is_empty() {
+        return Err(invalid_data(
+            "the file ends in the middle of a facet".to_string(),
+        ));
+    }
+    if triangles.is_empty() {
+        return Err(invalid_data("the file has no facets".to_string()));
+    }
+    Ok(triangles)
+}
+
+//read an ascii or binary stl. binary files can also start with "solid", so the size of the file decides
+pub fn parse_stl(bytes: &[u8]) -> Result<Mesh> {
+    let is_binary = bytes.len() >= HEADER_SIZE + 4 && {
+        let count =
+            u32::from_le_bytes(bytes[HEADER_SIZE..HEADER_SIZE + 4].try_into().unwrap()) as usize;
+        bytes.len() == HEADER_SIZE + 4 + count * TRIANGLE_SIZE || !bytes.starts_with(b"solid")
+    };
+
+    let triangles = match is_binary {
+        true => parse_binary_stl(bytes)?,
+        false => {
+            let content = std::str::from_utf8(bytes)
+                .map_err(|_| invalid_data("not a binary stl nor an ascii one".to_string()))?;
+            parse_ascii_stl(content)?
+        }
+    };
+    Ok(Mesh::from_triangles(&triangles, WIREFRAME_COLOR))
+}
+
+pub fn read_stl(path: &str) -> Result<Mesh> {
+    parse_stl(&fs::read(path)?)
+}
+
+fn face_corners(mesh: &Mesh, face: &(usize, usize, usize)) -> [(f64, f64, f64); 3] {
+    [
+        point_to_vector(mesh.vertices[face.0]),
+        point_to_vector(mesh.vertices[face.1]),
+        point_to_vector(mesh.vertices[face.2]),
+    ]
+}
+
+fn stl_binary(mesh: &Mesh) -> Vec<u8> {
+    let mut bytes: Vec<u8> = vec![0; HEADER_SIZE];
+    let header = b"binary stl written by cglib";
+    bytes[..header.len()].copy_from_slice(header);
+    bytes.extend_from_slice(&(mesh.faces.len() as u32).to_le_bytes());
+
+    for (face, normal) in mesh.faces.iter().zip(mesh.face_normals()) {
+        let corners = face_corners(mesh, face);
+        for (x, y, z) in [normal, corners[0], corners[1], corners[2]] {
+            for value in [x, y, z] {
+                bytes.extend_from_slice(&(value as f32).to_le_bytes());
+            }
+        }
+        bytes.extend_from_slice(&[0, 0]);
+    }
+    bytes
+}
+
+fn stl_ascii(mesh: &Mesh, name: &str) -> String {
+    let mut content = format!("solid {}\n", name);
+    for (face, normal) in mesh.faces.iter().zip(mesh.face_normals()) {
+        content.push_str(&format!(
+            "  facet normal {} {} {}\n    outer loop\n",
+            normal.0, normal.1, normal.2
+        ));
+        for (x, y, z) in face_corners(mesh, face) {
+            content.push_str(&format!("      vertex {} {} {}\n", x, y, z));
+        }
+        content.push_str("    endloop\n  endfacet\n");
+    }
+    content.push_str(&format!("endsolid {}\n", name));
+    content
+}
+
+//write the faces of a mesh as a binary or ascii stl, stl has no place for edges, colors or uvs
+pub fn write_stl(mesh: &Mesh, path: &str, binary: bool) -> Result<()> {
+    if mesh.faces.is_empty() {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            "the mesh has no faces to write",
+        ));
+    }
+    match binary {
+        true => fs::write(path, stl_binary(mesh)),
+        false => fs::write(path, stl_ascii(mesh, "cglib")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn two_triangles() -> Mesh {
+        Mesh::from_triangles(
+            &[
+                [(0., 0., 0., 1.), (1., 0., 0., 1.), (0., 1., 0., 1.)],
+                [(1., 0., 0., 1.), (1., 1., 0.5, 1.), (0., 1., 0., 1.)],
+            ],
+            WIREFRAME_COLOR,
+        )
+    }
+
+    #[test]
+    fn meshes_round_trip_in_both_formats() {
+        let mesh = two_triangles();
+        for binary in [false, true] {
+            let path = std::env::temp_dir().join(format!("cglib_round_trip_{}.stl", binary));
+            let path = path.to_str().unwrap();
+            write_stl(&mesh, path, binary).unwrap();
+            let read = read_stl(path).unwrap();
+            fs::remove_file(path).unwrap();
+            //the shared corners are merged again
+            assert_eq!(read.vertices, mesh.vertices);
+            assert_eq!(read.faces, mesh.faces);
+        }
+    }
+
+    #[test]
+    fn binary_files_may_start_with_solid() {
+        let mut bytes = stl_binary(&two_triangles());
+        bytes[..5].copy_from_slice(b"solid");
+        assert_eq!(parse_stl(&bytes).unwrap().faces.len(), 2);
+        //a triangle missing at the end
+        bytes[..5].copy_from_slice(b"cglib");
+        bytes.truncate(bytes.len() - TRIANGLE_SIZE);
+        assert!(parse_stl(&bytes).is_err());
+    }
+
+    #[test]
+    fn files_without_whole_facets_are_an_error() {
+        let ascii = stl_ascii(&two_triangles(), "cglib");
+        assert!(parse_stl(b"").is_err());
+        assert!(parse_stl(b"too short for a binary stl").is_err());
+        assert!(parse_stl(b"solid cglib\nendsolid cglib\n").is_err());
+        assert!(parse_stl(ascii.replacen("solid", "model", 1).as_bytes()).is_err());
+        //cut in the middle of the second facet
+        let cut = ascii.rfind("endloop").unwrap();
+        assert!(parse_stl(ascii[..cut].as_bytes()).is_err());
+        assert_eq!(parse_stl(ascii.as_bytes()).unwrap().faces.len(), 2);
+    }
+}
@@ -0,0 +1,339 @@
+use crate::common::Vector3;
+use crate::operations::{rotation_matrix_3d, scale_matrix_3d, translation_matrix_3d};
+use crate::reflection::{coordinate_plane_normal, line_reflection_matrix_2d, reflection_matrix};
+use crate::rotation::{axis_angle_matrix, check_axis, euler_from_column_matrix, Quaternion};
+use crate::vector::{cross, dot, length, scale, subtract};
+
+use ndarray::{arr2, Array2, ArrayBase, Dim, OwnedRepr};
+use pyo3::exceptions::{PyIndexError, PyValueError};
+use pyo3::prelude::*;
+
+pub fn matrix_to_array(matrix: ArrayBase<OwnedRepr<f64>, Dim<[usize; 2]>>) -> [[f64; 4]; 4] {
+    let mut array = [[0.; 4]; 4];
+    for (i, row) in array.iter_mut().enumerate() {
+        for (j, value) in row.iter_mut().enumerate() {
+            *value = matrix[[i, j]];
+        }
+    }
+    array
+}
+
+//invert a 4x4 matrix with gauss-jordan elimination, returns None when it is singular
+pub fn invert_matrix(
+    matrix: &ArrayBase<OwnedRepr<f64>, Dim<[usize; 2]>>,
+) -> Option<ArrayBase<OwnedRepr<f64>, Dim<[usize; 2]>>> {
+    let mut left = matrix.clone();
+    let mut right: ArrayBase<OwnedRepr<f64>, Dim<[usize; 2]>> = Array2::eye(4);
+
+    for column in 0..4 {
+        //use the biggest pivot available to keep the elimination stable
+        let pivot = (column..4)
+            .max_by(|a, b| {
+                left[[*a, column]]
+                    .abs()
+                    .total_cmp(&left[[*b, column]].abs())
+            })
+            .unwrap();
+        if left[[pivot, column]].abs() < 1e-12 {
+            return None;
+        }
+        for j in 0..4 {
+            left.swap([column, j], [pivot, j]);
+            right.swap([column, j], [pivot, j]);
+        }
+
+        let divisor = left[[column, column]];
+        for j in 0..4 {
+            left[[column, j]] /= divisor;
+            right[[column, j]] /= divisor;
+        }
+        for i in 0..4 {
+            if i == column {
+                continue;
+            }
+            let factor = left[[i, column]];
+            for j in 0..4 {
+                left[[i, j]] -= factor * left[[column, j]];
+                right[[i, j]] -= factor * right[[column, j]];
+            }
+        }
+    }
+    Some(right)
+}
+
+//split an affine matrix into (translation, rotation, scale, shear), returns None when it is degenerate.
+//the rotation is given as euler angles in degrees, made around x, then y, then z the way Quaternion.from_euler
+//takes them, and the shear as the (xy, xz, yz) factors. scaling, then shearing, then rotating and then
+//translating rebuilds the matrix
+pub fn decompose_matrix(
+    m: &ArrayBase<OwnedRepr<f64>, Dim<[usize; 2]>>,
+) -> Option<(Vector3, Vector3, Vector3, Vector3)> {
+    let translation = (m[[3, 0]], m[[3, 1]], m[[3, 2]]);
+    let row = |i: usize| -> Vector3 { (m[[i, 0]], m[[i, 1]], m[[i, 2]]) };
+
+    //gram-schmidt over the rows separates the scale and shear from the rotation
+    let mut scale_x = length(row(0));
+    if scale_x == 0. {
+        return None;
+    }
+    let mut r0 = scale(row(0), 1. / scale_x);
+
+    let shear_xy = dot(row(1), r0);
+    let mut r1 = subtract(row(1), scale(r0, shear_xy));
+    let mut scale_y = length(r1);
+    if scale_y == 0. {
+        return None;
+    }
+    r1 = scale(r1, 1. / scale_y);
+
+    let shear_xz = dot(row(2), r0);
+    let shear_yz = dot(row(2), r1);
+    let mut r2 = subtract(subtract(row(2), scale(r0, shear_xz)), scale(r1, shear_yz));
+    let mut scale_z = length(r2);
+    if scale_z == 0. {
+        return None;
+    }
+    r2 = scale(r2, 1. / scale_z);
+
+    //a mirrored matrix is kept as a rotation with negative scales
+    if dot(r0, cross(r1, r2)) < 0. {
+        scale_x = -scale_x;
+        scale_y = -scale_y;
+        scale_z = -scale_z;
+        r0 = scale(r0, -1.);
+        r1 = scale(r1, -1.);
+        r2 = scale(r2, -1.);
+    }
+
+    Some((
+        translation,
+        //the rows of a matrix for row vectors are the columns of the usual one
+        euler_from_column_matrix(
+            &[[r0.0, r1.0, r2.0], [r0.1, r1.1, r2.1], [r0.2, r1.2, r2.2]],
+            [0, 1, 2],
+        ),
+        (scale_x, scale_y, scale_z),
+        (
+            shear_xy / scale_y.abs(),
+            shear_xz / scale_z.abs(),
+            shear_yz / scale_z.abs(),
+        ),
+    ))
+}
+
+//a 4x4 transformation matrix, together with a stack to save and restore it.
+//every operation is applied after the ones already in the matrix, as the points are row vectors
+#[pyclass]
+#[derive(Clone)]
+pub struct Transform {
+    pub matrix: ArrayBase<OwnedRepr<f64>, Dim<[usize; 2]>>,
+    stack: Vec<ArrayBase<OwnedRepr<f64>, Dim<[usize; 2]>>>,
+}
+
+impl Transform {
+    pub fn from_matrix(matrix: ArrayBase<OwnedRepr<f64>, Dim<[usize; 2]>>) -> Self {
+        Transform {
+            matrix,
+            stack: vec![],
+        }
+    }
+
+    pub fn then(&mut self, matrix: &ArrayBase<OwnedRepr<f64>, Dim<[usize; 2]>>) {
+        self.matrix = self.matrix.dot(matrix);
+    }
+}
+
+#[pymethods]
+impl Transform {
+    #[new]
+    #[args(matrix = "None")]
+    fn new(matrix: Option<[[f64; 4]; 4]>) -> Self {
+        match matrix {
+            Some(matrix) => Transform::from_matrix(arr2(&matrix)),
+            None => Transform::from_matrix(Array2::eye(4)),
+        }
+    }
+
+    #[getter]
+    fn matrix(&self) -> [[f64; 4]; 4] {
+        matrix_to_array(self.matrix.clone())
+    }
+
+    //apply another transform after this one
+    fn compose(&mut self, other: &Transform) {
+        self.then(&other.matrix);
+    }
+
+    fn translate(&mut self, x: f64, y: f64, z: f64) {
+        self.then(&translation_matrix_3d(x, y, z));
+    }
+
+    fn scale(&mut self, x: f64, y: f64, z: f64) {
+        self.then(&scale_matrix_3d([x, y, z, 1.]));
+    }
+
+    fn rotate(&mut self, degrees: f64, axis: char) -> PyResult<()> {
+        check_axis(axis)?;
+        self.then(&rotation_matrix_3d(degrees, axis));
+        Ok(())
+    }
+
+    //rotate around any axis going through the pivot
+    #[args(pivot = "(0., 0., 0.)")]
+    fn rotate_axis(&mut self, degrees: f64, axis: Vector3, pivot: Vector3) -> PyResult<()> {
+        if length(axis) == 0. {
+            return Err(PyValueError::new_err("the rotation axis can not be zero"));
+        }
+        self.then(&axis_angle_matrix(degrees, axis, pivot));
+        Ok(())
+    }
+
+    #[args(pivot = "(0., 0., 0.)")]
+    fn rotate_quaternion(&mut self, quaternion: &Quaternion, pivot: Vector3) {
+        self.then(&quaternion.matrix(pivot));
+    }
+
+    //mirror through one of the coordinate planes: "xy", "yz" or "xz"
+    fn reflect(&mut self, plane: &str) -> PyResult<()> {
+        match coordinate_plane_normal(plane) {
+            Some(normal) => {
+                self.then(&reflection_matrix((0., 0., 0.), normal));
+                Ok(())
+            }
+            None => Err(PyValueError::new_err(format!(
+                "the plane must be 'xy', 'yz' or 'xz', got '{}'",
+                plane
+            ))),
+        }
+    }
+
+    //mirror through the plane that goes through the point with the given normal
+    fn reflect_plane(&mut self, point: Vector3, normal: Vector3) -> PyResult<()> {
+        if length(normal) == 0. {
+            return Err(PyValueError::new_err("the plane normal can not be zero"));
+        }
+        self.then(&reflection_matrix(point, normal));
+        Ok(())
+    }
+
+    //mirror the xy plane across the line that goes through the point in the given direction
+    fn reflect_line(&mut self, point: (f64, f64), direction: (f64, f64)) -> PyResult<()> {
+        if direction == (0., 0.) {
+            return Err(PyValueError::new_err("the line direction can not be zero"));
+        }
+        self.then(&line_reflection_matrix_2d(point, direction));
+        Ok(())
+    }
+
+    //each factor moves the first axis by the second one, xy is x' = x + xy * y
+    #[args(xy = "0.", xz = "0.", yx = "0.", yz = "0.", zx = "0.", zy = "0.")]
+    fn shear(&mut self, xy: f64, xz: f64, yx: f64, yz: f64, zx: f64, zy: f64) {
+        // rustfmt-ignore
+        let matrix: [[f64; 4]; 4] = [
+            [1., yx, zx, 0.],
+            [xy, 1., zy, 0.],
+            [xz, yz, 1., 0.],
+            [0., 0., 0., 1.],
+        ];
+        self.then(&arr2(&matrix));
+    }
+
+    //save the current matrix
+    fn push(&mut self) {
+        self.stack.push(self.matrix.clone());
+    }
+
+    //restore the last saved matrix
+    fn pop(&mut self) -> PyResult<()> {
+        match self.stack.pop() {
+            Some(matrix) => {
+                self.matrix = matrix;
+                Ok(())
+            }
+            None => Err(PyIndexError::new_err("pop from an empty transform stack")),
+        }
+    }
+
+    fn invert(&self) -> PyResult<Transform> {
+        match invert_matrix(&self.matrix) {
+            Some(matrix) => Ok(Transform::from_matrix(matrix)),
+            None => Err(PyValueError::new_err("the transform is not invertible")),
+        }
+    }
+
+    //split an affine transform into (translation, rotation, scale, shear), see decompose_matrix
+    fn decompose(&self) -> PyResult<(Vector3, Vector3, Vector3, Vector3)> {
+        match decompose_matrix(&self.matrix) {
+            Some(parts) => Ok(parts),
+            None => Err(PyValueError::new_err("the transform is degenerate")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(
+        a: &ArrayBase<OwnedRepr<f64>, Dim<[usize; 2]>>,
+        b: &ArrayBase<OwnedRepr<f64>, Dim<[usize; 2]>>,
+    ) {
+        assert!(
+            a.iter().zip(b.iter()).all(|(x, y)| (x - y).abs() < 1e-9),
+            "{} != {}",
+            a,
+            b
+        );
+    }
+
+    fn shear_matrix(shear: Vector3) -> ArrayBase<OwnedRepr<f64>, Dim<[usize; 2]>> {
+        arr2(&[
+            [1., 0., 0., 0.],
+            [shear.0, 1., 0., 0.],
+            [shear.1, shear.2, 1., 0.],
+            [0., 0., 0., 1.],
+        ])
+    }
+
+    fn compose(
+        translation: Vector3,
+        euler: Vector3,
+        scales: Vector3,
+        shear: Vector3,
+    ) -> ArrayBase<OwnedRepr<f64>, Dim<[usize; 2]>> {
+        scale_matrix_3d([scales.0, scales.1, scales.2, 1.])
+            .dot(&shear_matrix(shear))
+            .dot(&Quaternion::from_euler_order(euler, [0, 1, 2]).matrix((0., 0., 0.)))
+            .dot(&translation_matrix_3d(
+                translation.0,
+                translation.1,
+                translation.2,
+            ))
+    }
+
+    #[test]
+    fn inverse_undoes_the_matrix() {
+        let matrix = compose(
+            (1., -2., 3.),
+            (10., 20., 30.),
+            (2., 3., 0.5),
+            (0.3, 0., -0.2),
+        );
+        let inverse = invert_matrix(&matrix).unwrap();
+        assert_close(&matrix.dot(&inverse), &Array2::eye(4));
+        assert_close(&inverse.dot(&matrix), &Array2::eye(4));
+        //a projection flattens one axis and has no inverse
+        assert!(invert_matrix(&scale_matrix_3d([1., 1., 0., 1.])).is_none());
+    }
+
+    #[test]
+    fn decomposed_parts_rebuild_the_matrix() {
+        for scales in [(2., 3., 0.5), (-2., 3., 0.5)] {
+            let matrix = compose((1., -2., 3.), (10., 20., 30.), scales, (0.3, 0.1, -0.2));
+            let (translation, euler, scales, shear) = decompose_matrix(&matrix).unwrap();
+            assert_eq!(translation, (1., -2., 3.));
+            assert_close(&compose(translation, euler, scales, shear), &matrix);
+        }
+        assert!(decompose_matrix(&scale_matrix_3d([1., 0., 1., 1.])).is_none());
+    }
+}
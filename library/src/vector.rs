@@ -0,0 +1,45 @@
+use crate::common::Vector3;
+
+//basic operations over 3d vectors
+
+pub fn add(a: Vector3, b: Vector3) -> Vector3 {
+    (a.0 + b.0, a.1 + b.1, a.2 + b.2)
+}
+
+pub fn subtract(a: Vector3, b: Vector3) -> Vector3 {
+    (a.0 - b.0, a.1 - b.1, a.2 - b.2)
+}
+
+pub fn scale(a: Vector3, factor: f64) -> Vector3 {
+    (a.0 * factor, a.1 * factor, a.2 * factor)
+}
+
+pub fn dot(a: Vector3, b: Vector3) -> f64 {
+    a.0 * b.0 + a.1 * b.1 + a.2 * b.2
+}
+
+pub fn cross(a: Vector3, b: Vector3) -> Vector3 {
+    (
+        a.1 * b.2 - a.2 * b.1,
+        a.2 * b.0 - a.0 * b.2,
+        a.0 * b.1 - a.1 * b.0,
+    )
+}
+
+//point at t of the way from a to b
+pub fn lerp(a: Vector3, b: Vector3, t: f64) -> Vector3 {
+    add(a, scale(subtract(b, a), t))
+}
+
+pub fn length(a: Vector3) -> f64 {
+    dot(a, a).sqrt()
+}
+
+//returns the vector itself when its length is zero
+pub fn normalize(a: Vector3) -> Vector3 {
+    let norm = length(a);
+    if norm == 0. {
+        return a;
+    }
+    scale(a, 1. / norm)
+}
@@ -0,0 +1,206 @@
+use crate::common::{Edge, Point};
+use crate::operations::SCREEN_DEPTH;
+
+use ndarray::{arr2, ArrayBase, Dim, OwnedRepr};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+//maps a window of the world, in any units and with the y axis pointing up,
+//to an area of the image in pixels, where the y axis points down
+#[pyclass]
+#[derive(Clone)]
+pub struct WindowViewport {
+    //world window as (x_min, y_min, x_max, y_max)
+    #[pyo3(get)]
+    pub window: (f64, f64, f64, f64),
+    //area of the image as ((left, top), (right, bottom)), the right and bottom pixels are excluded.
+    //when it is None the whole image is used
+    #[pyo3(get, set)]
+    pub viewport: Option<Edge>,
+    //shrink the viewport to the aspect ratio of the window, centered, so the drawing is not stretched
+    #[pyo3(get, set)]
+    pub keep_aspect: bool,
+}
+
+impl WindowViewport {
+    pub fn window_aspect(&self) -> f64 {
+        let (x_min, y_min, x_max, y_max) = self.window;
+        (x_max - x_min) / (y_max - y_min)
+    }
+
+    //world window to normalized device coordinates, z is kept with the same depth range as the screen
+    pub fn window_matrix(&self) -> ArrayBase<OwnedRepr<f64>, Dim<[usize; 2]>> {
+        let (x_min, y_min, x_max, y_max) = self.window;
+        let scale_x = 2. / (x_max - x_min);
+        let scale_y = 2. / (y_max - y_min);
+        // rustfmt-ignore
+        let matrix: [[f64; 4]; 4] = [
+            [scale_x, 0., 0., 0.],
+            [0., scale_y, 0., 0.],
+            [0., 0., 1. / SCREEN_DEPTH, 0.],
+            [-1. - x_min * scale_x, -1. - y_min * scale_y, 0., 1.],
+        ];
+        arr2(&matrix)
+    }
+
+    //the area of the image things are drawn into, letterboxed to the given aspect ratio when keep_aspect is set
+    pub fn device_area(&self, width: usize, height: usize, aspect: f64) -> Edge {
+        let ((left, top), (right, bottom)) = self
+            .viewport
+            .unwrap_or(((0, 0), (width as i32, height as i32)));
+        let (mut left, mut top) = (left as f64, top as f64);
+        let (mut area_width, mut area_height) = ((right as f64 - left), (bottom as f64 - top));
+
+        if self.keep_aspect && aspect > 0. && area_height > 0. {
+            if area_width / area_height > aspect {
+                let new_width = area_height * aspect;
+                left += (area_width - new_width) / 2.;
+                area_width = new_width;
+            } else {
+                let new_height = area_width / aspect;
+                top += (area_height - new_height) / 2.;
+                area_height = new_height;
+            }
+        }
+        (
+            (left.round() as i32, top.round() as i32),
+            (
+                (left + area_width).round() as i32,
+                (top + area_height).round() as i32,
+            ),
+        )
+    }
+}
+
+//the whole image as a device area
+pub fn full_area(width: usize, height: usize) -> Edge {
+    ((0, 0), (width as i32, height as i32))
+}
+
+//normalized device coordinates to the pixels of an area, inverting the y axis.
+//the result is not rounded, the center of each pixel is at its integer coordinates
+pub fn ndc_to_device_exact(x: f64, y: f64, area: &Edge) -> (f64, f64) {
+    let ((left, top), (right, bottom)) = *area;
+    let x_max = (right - left - 1).max(1) as f64;
+    let y_max = (bottom - top - 1).max(1) as f64;
+    (
+        left as f64 + (x + 1.) / 2. * x_max,
+        top as f64 + (1. - y) / 2. * y_max,
+    )
+}
+
+//the pixels of an area to normalized device coordinates, the inverse of ndc_to_device_exact
+pub fn device_to_ndc(x: f64, y: f64, area: &Edge) -> (f64, f64) {
+    let ((left, top), (right, bottom)) = *area;
+    let x_max = (right - left - 1).max(1) as f64;
+    let y_max = (bottom - top - 1).max(1) as f64;
+    (
+        (x - left as f64) / x_max * 2. - 1.,
+        1. - (y - top as f64) / y_max * 2.,
+    )
+}
+
+//normalized device coordinates to the pixels of an area, inverting the y axis
+pub fn ndc_to_device(x: f64, y: f64, area: &Edge) -> Point {
+    let (device_x, device_y) = ndc_to_device_exact(x, y, area);
+    (device_x.round() as i32, device_y.round() as i32)
+}
+
+//a window with no width or height can not be stretched over the viewport
+fn check_window(window: (f64, f64, f64, f64)) -> PyResult<()> {
+    let (x_min, y_min, x_max, y_max) = window;
+    if x_min == x_max || y_min == y_max {
+        return Err(PyValueError::new_err(
+            "the window must have a width and a height",
+        ));
+    }
+    Ok(())
+}
+
+#[pymethods]
+impl WindowViewport {
+    #[new]
+    #[args(viewport = "None", keep_aspect = "true")]
+    fn new(
+        window: (f64, f64, f64, f64),
+        viewport: Option<Edge>,
+        keep_aspect: bool,
+    ) -> PyResult<Self> {
+        check_window(window)?;
+        Ok(WindowViewport {
+            window,
+            viewport,
+            keep_aspect,
+        })
+    }
+
+    #[setter]
+    fn set_window(&mut self, window: (f64, f64, f64, f64)) -> PyResult<()> {
+        check_window(window)?;
+        self.window = window;
+        Ok(())
+    }
+
+    //map 2d world points to the pixels of an image with the given size
+    fn map_points(&self, points: Vec<(f64, f64)>, width: usize, height: usize) -> Vec<Point> {
+        let matrix = self.window_matrix();
+        let area = self.device_area(width, height, self.window_aspect());
+        points
+            .iter()
+            .map(|(x, y)| {
+                let ndc_x = x * matrix[[0, 0]] + matrix[[3, 0]];
+                let ndc_y = y * matrix[[1, 1]] + matrix[[3, 1]];
+                ndc_to_device(ndc_x, ndc_y, &area)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mesh::{Mesh, WIREFRAME_COLOR};
+    use crate::operations::draw_mesh_wireframe;
+
+    fn viewport(viewport: Option<Edge>, keep_aspect: bool) -> WindowViewport {
+        WindowViewport {
+            window: (-1., -1., 1., 1.),
+            viewport,
+            keep_aspect,
+        }
+    }
+
+    #[test]
+    fn device_area_is_letterboxed_to_the_aspect() {
+        let area = viewport(None, true).device_area(200, 100, 1.);
+        assert_eq!(area, ((50, 0), (150, 100)));
+        let area = viewport(None, false).device_area(200, 100, 1.);
+        assert_eq!(area, full_area(200, 100));
+    }
+
+    #[test]
+    fn ndc_round_trips_through_the_device() {
+        let area = ((10, 20), (110, 70));
+        let (x, y) = ndc_to_device_exact(0.25, -0.5, &area);
+        let (ndc_x, ndc_y) = device_to_ndc(x, y, &area);
+        assert!((ndc_x - 0.25).abs() < 1e-12 && (ndc_y + 0.5).abs() < 1e-12);
+        assert_eq!(ndc_to_device(-1., 1., &area), (10, 20));
+    }
+
+    #[test]
+    fn viewport_bigger_than_the_image_is_cropped() {
+        let mesh = Mesh::from_edges(&[
+            ((-1., -1., 0., 1.), (1., 1., 0., 1.)),
+            ((-1., 1., 0., 1.), (1., -1., 0., 1.)),
+        ]);
+        let image = vec![vec![[255; 4]; 50]; 50];
+        let viewport = viewport(Some(((-100, -100), (150, 150))), false);
+        let drawn = draw_mesh_wireframe(image, &mesh, None, Some(&viewport));
+        //the diagonals go through the image on their way from one corner of the viewport to the other
+        let near_drawn = |x: usize, y: usize| {
+            (x.saturating_sub(2)..=(x + 2).min(49)).any(|x| drawn[y][x] == WIREFRAME_COLOR)
+        };
+        //bresenham steps a pixel early on the diagonals, and leaves out the last point of a line
+        assert!((1..48).all(|y| near_drawn(y, y) && near_drawn(49 - y, y)));
+    }
+}
@@ -114,7 +114,7 @@ class Operations:
         image = Operations.get_img_pixels(image, w, h)
 
         image_result, edges_result = cglib.shear_object(
-            image, edges=edges,  matrix=matrix)
+            image, object=edges,  matrix=matrix)
 
         new_image = np.array(image_result, dtype=np.uint8).astype(np.uint8)
         img = QImage(new_image, w, h, QImage.Format.Format_RGBA8888)
@@ -127,7 +127,7 @@ class Operations:
         image = Operations.get_img_pixels(image, w, h)
 
         image_result, edges_result = cglib.rotate_object(
-            image, edges=edges,  degrees=angle, axis=axis, center=aroundItself)
+            image, object=edges,  degrees=angle, axis=axis, center=aroundItself)
         new_image = np.array(image_result, dtype=np.uint8).astype(np.uint8)
         img = QImage(new_image, w, h, QImage.Format.Format_RGBA8888)
         return img, edges_result
@@ -139,7 +139,7 @@ class Operations:
         image = Operations.get_img_pixels(image, w, h)
 
         image_result, edges_result = cglib.translate_object(
-            image, edges=edges,  axis=axis)
+            image, object=edges,  axis=axis)
         new_image = np.array(image_result, dtype=np.uint8).astype(np.uint8)
         img = QImage(new_image, w, h, QImage.Format.Format_RGBA8888)
         return img, edges_result
@@ -151,7 +151,7 @@ class Operations:
         image = Operations.get_img_pixels(image, w, h)
 
         image_result, edges_result = cglib.scale_object(
-            image, edges=edges,  scale=scale)
+            image, object=edges,  scale=scale)
         new_image = np.array(image_result, dtype=np.uint8).astype(np.uint8)
         img = QImage(new_image, w, h, QImage.Format.Format_RGBA8888)
         return img, edges_result
@@ -187,8 +187,12 @@ class Operations:
 
         image = Operations.get_img_pixels(image, w, h)
 
+        # a white lamp of intensity il, attenuated by 1 / (d + k) in the phong models
+        attenuation = (1., 0., 0.) if model == 1 else (k, 1., 0.)
+        lamp = cglib.Light.point((100., 0., 100.), [il, il, il], attenuation)
+
         image_result = cglib.apply_luminosity(
-            image=image, model=model, kd_1=kd_1, ks_1=ks_1, kd_2=kd_2, ks_2=ks_2, k=k, ia=ia, ka=ka, il=il, n=n)
+            image=image, model=model, kd_1=kd_1, ks_1=ks_1, kd_2=kd_2, ks_2=ks_2, ia=ia, ka=ka, n=n, lights=[lamp])
 
         new_image = np.array(image_result, dtype=np.uint8).astype(np.uint8)
         img = QImage(new_image, w, h, QImage.Format.Format_RGBA8888)